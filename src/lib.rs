@@ -1,6 +1,9 @@
-use std::time::{Instant, Duration};
+use std::{
+    path::PathBuf,
+    time::{Instant, Duration},
+};
 
-use glam::vec3a;
+use glam::{vec3, vec3a, Mat4, Quat};
 use winit::{
     event::*,
     event_loop::{EventLoop, ControlFlow},
@@ -23,29 +26,47 @@ pub const GRID_DIMENSIONS: (u32, u32) = (20, 20);
 pub const CUBE_DIMENSIONS: (u32, u32, u32) = (20, 20, 20);
 
 
+/// Preprocessor with the crate constants available to shaders as defines
+///
+/// `INSTANCE_COLOR` is enabled so particles show their instance colors and the color
+/// map, undefine it to draw every particle in the same color.
+pub fn shader_preprocessor() -> ShaderPreprocessor {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor
+        .enable("INSTANCE_COLOR")
+        .define("PARTICLE_SIZE", PARTICLE_SIZE)
+        .define("GRID_WIDTH", GRID_DIMENSIONS.0)
+        .define("GRID_HEIGHT", GRID_DIMENSIONS.1)
+        .define("CUBE_WIDTH", CUBE_DIMENSIONS.0)
+        .define("CUBE_HEIGHT", CUBE_DIMENSIONS.1)
+        .define("CUBE_DEPTH", CUBE_DIMENSIONS.2);
+
+    preprocessor
+}
+
+
 pub struct InitOutput {
     pub event_loop: EventLoop<()>,
     pub window: Window,
     pub aspect_ratio: f32,
 }
 
-pub fn init() -> InitOutput {
+pub fn init() -> Result<InitOutput, RendererError> {
     env_logger::init();
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
-        .with_resizable(false)
-        .build(&event_loop)
-        .unwrap();
+        .with_resizable(true)
+        .build(&event_loop)?;
 
     let winit::dpi::PhysicalSize{width, height} = window.inner_size();
     let aspect_ratio = width as f32 / height as f32;
     
-    InitOutput {
+    Ok(InitOutput {
         event_loop,
         window,
         aspect_ratio
-    }
+    })
 }
 
 pub fn init_ui(state: &State, font_size: f64) -> (imgui::Context, imgui_winit_support::WinitPlatform, imgui_wgpu::Renderer) {
@@ -54,14 +75,14 @@ pub fn init_ui(state: &State, font_size: f64) -> (imgui::Context, imgui_winit_su
 
     platform.attach_window(
         ctxt.io_mut(), 
-        &state.window, 
+        state.window(), 
         imgui_winit_support::HiDpiMode::Default,
     );
 
     ctxt.set_ini_filename(None);
-    set_ui_size(&mut ctxt, &state.window);
+    set_ui_size(&mut ctxt, state.window());
 
-    let font_size = (font_size * state.window.scale_factor()) as f32;
+    let font_size = (font_size * state.window().scale_factor()) as f32;
     ctxt.fonts().add_font(&[imgui::FontSource::DefaultFontData { 
         config: Some(imgui::FontConfig {
             oversample_h: 1,
@@ -74,7 +95,9 @@ pub fn init_ui(state: &State, font_size: f64) -> (imgui::Context, imgui_winit_su
 
     let render_config = imgui_wgpu::RendererConfig {
         texture_format: state.surface_format,
-        depth_format: Some(wgpu::TextureFormat::Depth32Float),
+        // ui is drawn on top of the tonemapped image, straight into the surface
+        depth_format: None,
+        sample_count: 1,
         ..Default::default()
     };
 
@@ -88,7 +111,10 @@ pub fn set_ui_size(ctxt: &mut imgui::Context, window: &winit::window::Window){
 }
 
 pub fn handle_windowing(state: &mut State, imgui_ctxt: &mut imgui::Context, event: &WindowEvent, control_flow: &mut ControlFlow) {
-    if !state.input(event) {
+    // Keys typed into the ui, e.g. a space in a number field, aren't playback shortcuts
+    let ui_has_keyboard = imgui_ctxt.io().want_capture_keyboard;
+
+    if ui_has_keyboard || !state.input(event) {
         match event {
             WindowEvent::CloseRequested
             | WindowEvent::KeyboardInput {
@@ -99,14 +125,20 @@ pub fn handle_windowing(state: &mut State, imgui_ctxt: &mut imgui::Context, even
                         ..
                     },
                 ..
-            } => control_flow.set_exit(),
+            } => {
+                // Lets ffmpeg write the end of the video before the process goes away
+                if let Err(error) = state.stop_recording() {
+                    log::error!("failed to finish the recording: {error}");
+                }
+                control_flow.set_exit();
+            }
             WindowEvent::Resized(physical_size) => {
-                set_ui_size(imgui_ctxt, &state.window);
+                set_ui_size(imgui_ctxt, state.window());
                 state.resize(*physical_size);
             }
             WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                 // new_inner_size is &mut so w have to dereference it twice
-                set_ui_size(imgui_ctxt, &state.window);
+                set_ui_size(imgui_ctxt, state.window());
                 state.resize(**new_inner_size);
             }
             _ => {}
@@ -115,7 +147,9 @@ pub fn handle_windowing(state: &mut State, imgui_ctxt: &mut imgui::Context, even
 }
 
 pub fn handle_rendering(state: &mut State, imgui_renderer: &mut imgui_wgpu::Renderer, draw_data: &imgui::DrawData, control_flow: &mut ControlFlow) {
-    match state.render_with_ui(imgui_renderer, draw_data) {
+    let mut ui = ImguiOverlay { renderer: imgui_renderer, draw_data };
+
+    match state.render(&mut [&mut ui]) {
         Ok(_) => {}
         // Reconfigure the surface if it's lost or outdated
         Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => state.resize(state.size),
@@ -126,22 +160,30 @@ pub fn handle_rendering(state: &mut State, imgui_renderer: &mut imgui_wgpu::Rend
     }
 }
 
-pub async fn run() {
+/// What [`run`] starts with, the binary fills it in from the command line
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Directory of precomputed frames played instead of simulating, see [`State::open_cache`]
+    pub cache: Option<PathBuf>,
+    /// Particle file the simulation starts from instead of the default cube, see [`ParticleFrame::load`]
+    pub initial_frame: Option<PathBuf>,
+}
+
+pub async fn run(options: RunOptions) -> Result<(), RendererError> {
     env_logger::init();
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
-        .with_resizable(false)
-        .build(&event_loop)
-        .unwrap();
+        .with_resizable(true)
+        .build(&event_loop)?;
 
     let winit::dpi::PhysicalSize{width, height} = window.inner_size();
     let aspect_ratio = width as f32 / height as f32;
 
-    let shader_source = wgpu::ShaderSource::Wgsl(std::fs::read_to_string("src/shader.wgsl").unwrap().into());
+    let shader = Shader::with_preprocessor("src/shader.wgsl", shader_preprocessor())?;
     let vertices = Quad.scale(PARTICLE_SIZE);
     let indices = Quad::INDICES;
-    let instances = create_cube(0.1, CUBE_DIMENSIONS, None, (-1.0, -1.0, -2.0));
+    let instances = create_cube(&mut scene_rng(DEFAULT_SEED), 0.1, CUBE_DIMENSIONS, None, (-1.0, -1.0, -2.0));
     let camera = Camera {
         aspect: aspect_ratio,
         eye: vec3a(-4.0, 2.0, 2.0),
@@ -149,21 +191,39 @@ pub async fn run() {
         ..Default::default()
     };
 
-    let mut state = State::new(
-        window, 
-        shader_source, 
-        vertices.as_slice(), 
-        indices, 
-        instances, 
-        camera
-    ).await;
+    let mut builder = State::builder(window, shader.source())
+        .geometry(vertices.as_slice(), indices)
+        .instances(instances)
+        .camera(camera)
+        .sample_count(4);
+    if let Some(path) = options.initial_frame {
+        builder = builder.initial_frame(ParticleFrame::load(path)?);
+    }
+    let mut state = builder.build().await?;
+
+    // Floor under the simulation bounds, so the particles don't float in a void
+    let mut scene_meshes = MeshNode::new(&state)?;
+    let (bounds_min, bounds_max) = (state.solver.params.bounds_min, state.solver.params.bounds_max);
+    let floor_size = vec3(bounds_max.x - bounds_min.x, 0.1, bounds_max.z - bounds_min.z);
+    let floor_center = vec3((bounds_min.x + bounds_max.x) / 2.0, bounds_min.y - floor_size.y, (bounds_min.z + bounds_max.z) / 2.0);
+    scene_meshes.add_mesh(
+        &state.device,
+        &Geometry::cube().to_triangle_mesh(),
+        Mat4::from_scale_rotation_translation(floor_size, Quat::IDENTITY, floor_center),
+        MeshMaterial::default(),
+    );
+    state.add_render_node(scene_meshes)?;
+
+    if let Some(directory) = options.cache {
+        state.open_cache(directory)?;
+    }
     
     let (mut imgui_ctxt, mut imgui_platform, mut imgui_renderer) = init_ui(&state, 10.0);
     let mut frame_delta = Duration::new(0, 0);
 
     event_loop.run(move |event, _, control_flow| {
         let frame_start = Instant::now();
-        imgui_platform.handle_event(imgui_ctxt.io_mut(), &state.window, &event);
+        imgui_platform.handle_event(imgui_ctxt.io_mut(), state.window(), &event);
 
         match event {
             Event::WindowEvent {
@@ -175,17 +235,13 @@ pub async fn run() {
             Event::RedrawRequested(window_id) if window_id == state.window().id() => {
                 state.update();
 
-                imgui_platform.prepare_frame(imgui_ctxt.io_mut(), &state.window).expect("Failed to prepare ui frame");
+                imgui_platform.prepare_frame(imgui_ctxt.io_mut(), state.window()).expect("Failed to prepare ui frame");
                 imgui_ctxt.io_mut().update_delta_time(frame_delta);
                 let ui = imgui_ctxt.frame();
 
-                {
-                    ui.window("Info")
-                        .size([200.0, 100.0], imgui::Condition::FirstUseEver)
-                        .build(|| {
-                            ui.text("hello, world");
-                        });
-                }
+                parameter_panel(ui, &mut state);
+                profiler_panel(ui, &state.profiler);
+                playback_panel(ui, &mut state);
 
                 crate::handle_rendering(&mut state, &mut imgui_renderer, imgui_ctxt.render(), control_flow)
             },
@@ -1,5 +1,35 @@
-use fluid_renderer::run;
+use fluid_renderer::{run, RunOptions};
+
+const USAGE: &str = "usage: fluid-renderer [--initial <particle file>] [cache directory]";
 
 fn main() {
-    pollster::block_on(run());
+    let mut options = RunOptions::default();
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--initial") => match args.next() {
+                Some(path) => options.initial_frame = Some(path.into()),
+                None => exit_with_usage(),
+            },
+            Some("-h" | "--help") => {
+                println!("{USAGE}");
+                return;
+            }
+            // Unknown flags, e.g. typos, aren't taken for the directory
+            Some(flag) if flag.starts_with('-') => exit_with_usage(),
+            // A directory argument turns the renderer into a viewer of precomputed frames
+            _ if options.cache.is_none() => options.cache = Some(arg.into()),
+            _ => exit_with_usage(),
+        }
+    }
+
+    if let Err(error) = pollster::block_on(run(options)) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{USAGE}");
+    std::process::exit(2);
 }
@@ -1,17 +1,92 @@
+pub mod error;
+pub use error::*;
+
 pub mod state;
 pub use state::*;
 
+pub mod state_builder;
+pub use state_builder::*;
+
 pub mod generics;
 pub use generics::*;
 
+pub mod shader;
+pub use shader::*;
+
+pub mod post_process;
+pub use post_process::*;
+
 pub mod texture;
 pub use texture::*;
 
+pub mod render_targets;
+pub use render_targets::*;
+
+pub mod render_graph;
+pub use render_graph::*;
+
+pub mod render_nodes;
+pub use render_nodes::*;
+
+pub mod render_settings;
+pub use render_settings::*;
+
 pub mod shapes;
 pub use shapes::*;
 
+pub mod solver;
+pub use solver::*;
+
+pub mod playback;
+pub use playback::*;
+
+pub mod particle_frame;
+pub use particle_frame::*;
+
+pub mod vtk;
+pub use vtk::*;
+
+pub mod ply;
+pub use ply::*;
+
+pub mod csv;
+pub use csv::*;
+
+pub mod houdini;
+pub use houdini::*;
+
+pub mod checkpoint;
+pub use checkpoint::*;
+
+pub mod snapshot_cache;
+pub use snapshot_cache::*;
+
+pub mod frame_capture;
+pub use frame_capture::*;
+
+pub mod recorder;
+pub use recorder::*;
+
+pub mod image_diff;
+pub use image_diff::*;
+
 pub mod instances;
 pub use instances::*;
 
+pub mod mesh;
+pub use mesh::*;
+
+pub mod mesh_renderer;
+pub use mesh_renderer::*;
+
 pub mod simple_camera;
 pub use simple_camera::*;
+
+pub mod validation;
+pub use validation::*;
+
+pub mod profiler;
+pub use profiler::*;
+
+pub mod ui;
+pub use ui::*;
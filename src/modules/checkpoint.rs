@@ -0,0 +1,258 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+use glam::{Vec3, Vec3A};
+use crate::{State, Instance, Camera, SolverParams, ParticleSnapshot, with_header_capacity};
+
+
+/// Written after the magic bytes, bumped whenever the layout changes
+pub const CHECKPOINT_VERSION: u32 = 1;
+const CHECKPOINT_MAGIC: [u8; 4] = *b"FRCP";
+
+/// Where the ui saves and loads checkpoints
+pub const DEFAULT_CHECKPOINT_PATH: &str = "checkpoint.frcp";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(io::Error),
+    /// The file isn't a checkpoint
+    InvalidMagic,
+    /// Written with a layout this version can't read
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::InvalidMagic => write!(f, "not a checkpoint file"),
+            Self::UnsupportedVersion(version) => write!(f, "checkpoint version {version} isn't supported, expected version {CHECKPOINT_VERSION}"),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CheckpointError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+
+/// Everything a run depends on, restoring it continues bit for bit where the capture left off
+///
+/// The file is little endian: the magic `FRCP`, the version and then the fields in
+/// declaration order, floats as their exact bits so nothing is lost in the round trip.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub particles: ParticleSnapshot,
+    pub params: SolverParams,
+    /// Solver steps per fixed step of the playback
+    pub substeps: u32,
+    pub camera: Camera,
+}
+
+impl Checkpoint {
+    pub fn capture(state: &State) -> Self {
+        Checkpoint {
+            particles: ParticleSnapshot::capture(&state.instances, &state.solver),
+            params: state.solver.params,
+            substeps: state.playback.substeps,
+            camera: state.camera,
+        }
+    }
+
+    /// Replaces the simulation and camera of `state`, the rewind history is dropped
+    pub fn restore(&self, state: &mut State) {
+        let mut instances = Vec::new();
+        self.particles.restore(&mut instances, &mut state.solver);
+        state.resize_instances(instances);
+        state.solver.params = self.params;
+
+        state.playback.substeps = self.substeps;
+        state.playback.history.clear();
+        state.previous_positions.clear();
+
+        // The window may have changed size since the capture
+        state.camera = Camera { aspect: state.camera.aspect, ..self.camera };
+        state.update_camera();
+    }
+
+    pub fn write(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = CheckpointWriter(BufWriter::new(writer));
+        writer.bytes(&CHECKPOINT_MAGIC)?;
+        writer.u32(CHECKPOINT_VERSION)?;
+
+        let particles = &self.particles;
+        writer.u64(particles.instances.len() as u64)?;
+        for instance in &particles.instances {
+            writer.vec3(instance.position)?;
+            writer.vec3(instance.color)?;
+        }
+        writer.vec3s(&particles.velocities)?;
+        writer.f32s(&particles.densities)?;
+        writer.f32s(&particles.pressures)?;
+        writer.f32(particles.time)?;
+        writer.u64(particles.steps)?;
+
+        let params = &self.params;
+        for value in [
+            params.timestep, params.stiffness, params.viscosity, params.rest_density,
+            params.particle_spacing, params.smoothing_radius, params.restitution,
+        ] {
+            writer.f32(value)?;
+        }
+        writer.vec3(params.gravity)?;
+        writer.vec3(params.bounds_min)?;
+        writer.vec3(params.bounds_max)?;
+        writer.u32(self.substeps)?;
+
+        let camera = &self.camera;
+        writer.f32(camera.aspect)?;
+        for vector in [camera.eye, camera.target, camera.up] {
+            writer.vec3(vector.into())?;
+        }
+        writer.f32(camera.fovy)?;
+        writer.f32(camera.znear)?;
+        writer.f32(camera.zfar)?;
+
+        writer.0.flush()
+    }
+
+    pub fn read(reader: impl Read) -> Result<Self, CheckpointError> {
+        let mut reader = CheckpointReader(BufReader::new(reader));
+        if reader.bytes::<4>()? != CHECKPOINT_MAGIC {
+            return Err(CheckpointError::InvalidMagic);
+        }
+        match reader.u32()? {
+            CHECKPOINT_VERSION => {}
+            version => return Err(CheckpointError::UnsupportedVersion(version)),
+        }
+
+        let count = reader.u64()? as usize;
+        let mut instances = with_header_capacity(count);
+        for _ in 0..count {
+            instances.push(Instance { position: reader.vec3()?, color: reader.vec3()? });
+        }
+        let particles = ParticleSnapshot {
+            instances,
+            velocities: reader.vec3s(count)?,
+            densities: reader.f32s(count)?,
+            pressures: reader.f32s(count)?,
+            time: reader.f32()?,
+            steps: reader.u64()?,
+        };
+
+        let params = SolverParams {
+            timestep: reader.f32()?,
+            stiffness: reader.f32()?,
+            viscosity: reader.f32()?,
+            rest_density: reader.f32()?,
+            particle_spacing: reader.f32()?,
+            smoothing_radius: reader.f32()?,
+            restitution: reader.f32()?,
+            gravity: reader.vec3()?,
+            bounds_min: reader.vec3()?,
+            bounds_max: reader.vec3()?,
+        };
+        let substeps = reader.u32()?;
+
+        let camera = Camera {
+            aspect: reader.f32()?,
+            eye: Vec3A::from(reader.vec3()?),
+            target: Vec3A::from(reader.vec3()?),
+            up: Vec3A::from(reader.vec3()?),
+            fovy: reader.f32()?,
+            znear: reader.f32()?,
+            zfar: reader.f32()?,
+        };
+
+        Ok(Checkpoint { particles, params, substeps, camera })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write(File::create(path)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        Self::read(File::open(path)?)
+    }
+}
+
+
+struct CheckpointWriter<W: Write>(W);
+
+impl<W: Write> CheckpointWriter<W> {
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.write_all(bytes)
+    }
+
+    fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn vec3(&mut self, value: Vec3) -> io::Result<()> {
+        value.to_array().into_iter().try_for_each(|component| self.f32(component))
+    }
+
+    fn f32s(&mut self, values: &[f32]) -> io::Result<()> {
+        values.iter().try_for_each(|value| self.f32(*value))
+    }
+
+    fn vec3s(&mut self, values: &[Vec3]) -> io::Result<()> {
+        values.iter().try_for_each(|value| self.vec3(*value))
+    }
+}
+
+struct CheckpointReader<R: Read>(R);
+
+impl<R: Read> CheckpointReader<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.bytes().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        self.bytes().map(f32::from_le_bytes)
+    }
+
+    fn vec3(&mut self) -> io::Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn f32s(&mut self, count: usize) -> io::Result<Vec<f32>> {
+        (0..count).map(|_| self.f32()).collect()
+    }
+
+    fn vec3s(&mut self, count: usize) -> io::Result<Vec<Vec3>> {
+        (0..count).map(|_| self.vec3()).collect()
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use crate::{ParticleFrame, ParticleFileError, attribute_token};
+
+
+/// One row per particle under a header of the [`ParticleFrame::columns`], the time isn't stored
+pub fn write_csv(writer: impl Write, frame: &ParticleFrame) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let columns = frame.columns();
+
+    let header = columns.iter()
+        .map(|(name, _)| attribute_token(name))
+        .collect::<Vec<_>>();
+    writeln!(writer, "{}", header.join(","))?;
+
+    for index in 0..frame.len() {
+        let row = columns.iter()
+            .map(|(_, values)| values[index].to_string())
+            .collect::<Vec<_>>();
+        writeln!(writer, "{}", row.join(","))?;
+    }
+
+    writer.flush()
+}
+
+/// Reads numeric columns under a header row, see [`ParticleFrame::from_columns`]
+pub fn read_csv(reader: impl Read) -> Result<ParticleFrame, ParticleFileError> {
+    let mut columns: Vec<(String, Vec<f32>)> = Vec::new();
+
+    for (index, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = line.split(',')
+            .map(|field| field.trim().trim_matches('"'))
+            .collect::<Vec<_>>();
+
+        if columns.is_empty() {
+            columns = fields.into_iter().map(|name| (name.to_owned(), Vec::new())).collect();
+            continue;
+        }
+
+        if fields.len() != columns.len() {
+            return Err(ParticleFileError::parse(line_number, format!("expected {} values, found {}", columns.len(), fields.len())));
+        }
+
+        for (field, (name, values)) in fields.into_iter().zip(columns.iter_mut()) {
+            let value = field.parse()
+                .map_err(|_| ParticleFileError::parse(line_number, format!("invalid value `{field}` in column `{name}`")))?;
+            values.push(value);
+        }
+    }
+
+    ParticleFrame::from_columns(columns)
+}
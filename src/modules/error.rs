@@ -0,0 +1,133 @@
+use std::{fmt, path::PathBuf};
+use crate::{PreprocessError, PreprocessErrorKind, ShaderValidationError, RenderGraphError, MeshError, ParticleFileError, CheckpointError, SnapshotCacheError, RecorderError};
+
+
+#[derive(Debug)]
+pub enum RendererError {
+    /// No adapter matched the requested options
+    NoAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    CreateWindow(winit::error::OsError),
+    /// The shader or one of its includes couldn't be read
+    ShaderIo { path: PathBuf, error: std::io::Error },
+    /// Preprocessing, parsing, validation or pipeline creation failed
+    ShaderCompile(String),
+    RenderGraph(RenderGraphError),
+    Mesh(MeshError),
+    ParticleFile(ParticleFileError),
+    Checkpoint(CheckpointError),
+    SnapshotCache(SnapshotCacheError),
+    Recorder(RecorderError),
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter => write!(f, "no suitable graphics adapter found"),
+            Self::RequestDevice(error) => write!(f, "{error}"),
+            Self::CreateSurface(error) => write!(f, "{error}"),
+            Self::CreateWindow(error) => write!(f, "failed to create window: {error}"),
+            Self::ShaderIo { path, error } => write!(f, "failed to read shader {}: {error}", path.display()),
+            Self::ShaderCompile(message) => write!(f, "shader compilation failed: {message}"),
+            Self::RenderGraph(error) => write!(f, "{error}"),
+            Self::Mesh(error) => write!(f, "{error}"),
+            Self::ParticleFile(error) => write!(f, "{error}"),
+            Self::Checkpoint(error) => write!(f, "{error}"),
+            Self::SnapshotCache(error) => write!(f, "{error}"),
+            Self::Recorder(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::RequestDevice(error) => Some(error),
+            Self::CreateSurface(error) => Some(error),
+            Self::CreateWindow(error) => Some(error),
+            Self::ShaderIo { error, .. } => Some(error),
+            Self::RenderGraph(error) => Some(error),
+            Self::Mesh(error) => Some(error),
+            Self::ParticleFile(error) => Some(error),
+            Self::Checkpoint(error) => Some(error),
+            Self::SnapshotCache(error) => Some(error),
+            Self::Recorder(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RendererError {
+    fn from(error: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(error)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for RendererError {
+    fn from(error: wgpu::CreateSurfaceError) -> Self {
+        Self::CreateSurface(error)
+    }
+}
+
+impl From<winit::error::OsError> for RendererError {
+    fn from(error: winit::error::OsError) -> Self {
+        Self::CreateWindow(error)
+    }
+}
+
+impl From<PreprocessError> for RendererError {
+    fn from(error: PreprocessError) -> Self {
+        let message = error.to_string();
+
+        match error.kind {
+            PreprocessErrorKind::Io { path, error } => Self::ShaderIo { path, error },
+            _ => Self::ShaderCompile(message),
+        }
+    }
+}
+
+impl From<ShaderValidationError> for RendererError {
+    fn from(error: ShaderValidationError) -> Self {
+        match error {
+            ShaderValidationError::Preprocess(error) => error.into(),
+            error => Self::ShaderCompile(error.to_string()),
+        }
+    }
+}
+
+impl From<RenderGraphError> for RendererError {
+    fn from(error: RenderGraphError) -> Self {
+        Self::RenderGraph(error)
+    }
+}
+
+impl From<MeshError> for RendererError {
+    fn from(error: MeshError) -> Self {
+        Self::Mesh(error)
+    }
+}
+
+impl From<ParticleFileError> for RendererError {
+    fn from(error: ParticleFileError) -> Self {
+        Self::ParticleFile(error)
+    }
+}
+
+impl From<CheckpointError> for RendererError {
+    fn from(error: CheckpointError) -> Self {
+        Self::Checkpoint(error)
+    }
+}
+
+impl From<SnapshotCacheError> for RendererError {
+    fn from(error: SnapshotCacheError) -> Self {
+        Self::SnapshotCache(error)
+    }
+}
+
+impl From<RecorderError> for RendererError {
+    fn from(error: RecorderError) -> Self {
+        Self::Recorder(error)
+    }
+}
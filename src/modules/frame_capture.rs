@@ -0,0 +1,155 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+
+/// Pixels read back from the gpu, rows top to bottom in 8 bit rgba
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl CapturedFrame {
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Self {
+        debug_assert_eq!(data.len(), (width * height * 4) as usize);
+        CapturedFrame { width, height, data }
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()
+    }
+
+    /// Reads an 8 bit png, grayscale and rgb images get converted to rgba
+    pub fn load_png(path: impl AsRef<Path>) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+            _ => buffer.iter().flat_map(|gray| [*gray, *gray, *gray, 255]).collect(),
+        };
+
+        Ok(CapturedFrame::new(info.width, info.height, data))
+    }
+}
+
+
+/// Offscreen color target the render graph draws into instead of the surface, and the buffer it's copied to
+///
+/// Surfaces can't be copied from on every backend, windowed states without
+/// surface copies capture frames by rendering them a second time into this texture.
+pub struct FrameCapture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    padded_bytes_per_row: u32,
+}
+
+impl FrameCapture {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, size: (u32, u32)) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Rows of a texture copy have to be aligned, the padding is dropped when reading
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (size.0 * 4).div_ceil(alignment) * alignment;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Buffer"),
+            size: (padded_bytes_per_row * size.1) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        FrameCapture { texture, view, buffer, format, size, padded_bytes_per_row }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Records the copy of the texture into the readback buffer
+    pub fn copy(&self, encoder: &mut wgpu::CommandEncoder) {
+        self.copy_from(encoder, &self.texture);
+    }
+
+    /// Records the copy of `texture` into the readback buffer, it needs the size and format of the capture
+    pub fn copy_from(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.size.0,
+                height: self.size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Waits for the copy submitted last and returns it as rgba
+    pub fn read(&self, device: &wgpu::Device) -> Result<CapturedFrame, wgpu::BufferAsyncError> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        // The callback has run once the wait returns, a missing result means the map was dropped
+        receiver.recv().unwrap_or(Err(wgpu::BufferAsyncError))?;
+
+        let (width, height) = self.size;
+        let bgra = matches!(self.format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for row in mapped.chunks_exact(self.padded_bytes_per_row as usize) {
+                for pixel in row[..(width * 4) as usize].chunks_exact(4) {
+                    match bgra {
+                        true => data.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]),
+                        false => data.extend_from_slice(pixel),
+                    }
+                }
+            }
+        }
+        self.buffer.unmap();
+
+        Ok(CapturedFrame::new(width, height, data))
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+use crate::{ParticleFrame, ParticleFileError, AttributeValues, split_frame_number, attribute_token, with_header_capacity};
+
+
+/// Houdini names of the attributes it treats specially
+const HOUDINI_NAMES: [(&str, &str); 2] = [
+    (ParticleFrame::VELOCITY, "v"),
+    (ParticleFrame::COLOR, "Cd"),
+];
+
+fn houdini_name(name: &str) -> &str {
+    HOUDINI_NAMES.iter().find(|(ours, _)| *ours == name).map_or(name, |(_, houdini)| houdini)
+}
+
+fn frame_name(name: &str) -> &str {
+    HOUDINI_NAMES.iter().find(|(_, houdini)| *houdini == name).map_or(name, |(ours, _)| ours)
+}
+
+/// Classic ascii `.geo` that Houdini and partio read, the points without primitives
+///
+/// Velocities become `v`, colors `Cd` and every particle gets an integer `id`, taken from
+/// an `id` attribute when the frame has one and the particle index otherwise. The time
+/// is stored as a `time` detail attribute.
+pub fn write_geo(writer: impl Write, frame: &ParticleFrame) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let attributes = frame.attributes.iter()
+        .filter(|attribute| attribute.name != ParticleFrame::ID)
+        .collect::<Vec<_>>();
+    let ids = frame.scalar(ParticleFrame::ID);
+
+    writeln!(writer, "PGEOMETRY V5")?;
+    writeln!(writer, "NPoints {} NPrims 0", frame.len())?;
+    writeln!(writer, "NPointGroups 0 NPrimGroups 0")?;
+    writeln!(writer, "NPointAttrib {} NVertexAttrib 0 NPrimAttrib 0 NAttrib 1", attributes.len() + 1)?;
+
+    writeln!(writer, "PointAttrib")?;
+    for attribute in &attributes {
+        let name = attribute_token(houdini_name(&attribute.name));
+        match (&attribute.values, name.as_ref()) {
+            (AttributeValues::Vector(_), "v") => writeln!(writer, "v 3 vector 0 0 0")?,
+            (AttributeValues::Vector(_), name) => writeln!(writer, "{name} 3 float 0 0 0")?,
+            (AttributeValues::Scalar(_), name) => writeln!(writer, "{name} 1 float 0")?,
+        }
+    }
+    writeln!(writer, "id 1 int 0")?;
+
+    for (index, position) in frame.positions.iter().enumerate() {
+        write!(writer, "{} {} {} 1 (", position.x, position.y, position.z)?;
+        for attribute in &attributes {
+            match &attribute.values {
+                AttributeValues::Scalar(values) => write!(writer, "{}\t", values[index])?,
+                AttributeValues::Vector(values) => {
+                    let value = values[index];
+                    write!(writer, "{} {} {}\t", value.x, value.y, value.z)?;
+                }
+            }
+        }
+        let id = ids.map_or(index as i64, |ids| ids[index] as i64);
+        writeln!(writer, "{id})")?;
+    }
+
+    writeln!(writer, "DetailAttrib")?;
+    writeln!(writer, "time 1 float 0")?;
+    writeln!(writer, " ({})", frame.time)?;
+    writeln!(writer, "beginExtra")?;
+    writeln!(writer, "endExtra")?;
+
+    writer.flush()
+}
+
+
+struct GeoAttribute {
+    name: String,
+    size: usize,
+}
+
+/// Reads the point attribute definitions that follow a `PointAttrib` or `DetailAttrib` line
+fn read_attribute_definitions(lines: &mut impl Iterator<Item = (usize, String)>, count: usize) -> Result<Vec<GeoAttribute>, ParticleFileError> {
+    (0..count)
+        .map(|_| {
+            let (line_number, line) = lines.next().ok_or_else(|| ParticleFileError::parse(None, "geo attribute definitions ended early"))?;
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            match words.as_slice() {
+                [name, size, _ty, ..] => Ok(GeoAttribute {
+                    name: name.to_string(),
+                    size: size.parse().map_err(|_| ParticleFileError::parse(line_number, format!("invalid attribute size `{size}`")))?,
+                }),
+                _ => Err(ParticleFileError::parse(line_number, format!("invalid attribute definition `{line}`"))),
+            }
+        })
+        .collect()
+}
+
+/// Values inside the parentheses of a point or detail line, attributes are separated by whitespace
+fn parse_values(line_number: usize, values: &str, expected: usize) -> Result<Vec<f32>, ParticleFileError> {
+    let values = values.split_whitespace()
+        .map(|value| value.parse::<f32>().map_err(|_| ParticleFileError::parse(line_number, format!("invalid value `{value}`"))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values.len() == expected {
+        true => Ok(values),
+        false => Err(ParticleFileError::parse(line_number, format!("expected {expected} values, found {}", values.len()))),
+    }
+}
+
+/// Reads the points and their attributes of a classic ascii `.geo`, primitives are ignored
+///
+/// `v` and `Cd` become the velocity and color, 3 component attributes become vectors,
+/// others a scalar per component (`name` or `name_0`, `name_1`, ...).
+pub fn read_geo(reader: impl Read) -> Result<ParticleFrame, ParticleFileError> {
+    let mut lines = BufReader::new(reader)
+        .lines()
+        .enumerate()
+        .map(|(index, line)| line.map(|line| (index + 1, line)))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let mut header = String::new();
+    for _ in 0..4 {
+        let (_, line) = lines.next().ok_or_else(|| ParticleFileError::parse(None, "geo header ended early"))?;
+        header.push_str(&line);
+        header.push(' ');
+    }
+    if !header.starts_with("PGEOMETRY") {
+        return Err(ParticleFileError::parse(1, "not an ascii geo file"));
+    }
+
+    let words = header.split_whitespace().collect::<Vec<_>>();
+    let count = |key: &str| -> Result<usize, ParticleFileError> {
+        words.iter()
+            .position(|word| *word == key)
+            .and_then(|index| words.get(index + 1))
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| ParticleFileError::parse(None, format!("geo header has no {key}")))
+    };
+    let (point_count, attribute_count, detail_count) = (count("NPoints")?, count("NPointAttrib")?, count("NAttrib")?);
+
+    let attributes = match attribute_count {
+        0 => Vec::new(),
+        _ => {
+            lines.next();
+            read_attribute_definitions(&mut lines, attribute_count)?
+        }
+    };
+    let value_count = attributes.iter().map(|attribute| attribute.size).sum::<usize>();
+
+    let mut positions = with_header_capacity(point_count);
+    let mut values: Vec<Vec<f32>> = Vec::new();
+    for _ in 0..point_count {
+        let (line_number, line) = lines.next().ok_or_else(|| ParticleFileError::parse(None, "geo points ended early"))?;
+        let (position, attribute_values) = line.split_once('(').unwrap_or((&line, ""));
+
+        // The fourth value is the point weight Pw, not a homogeneous coordinate
+        let position = parse_values(line_number, position, 4)?;
+        positions.push(glam::Vec3::new(position[0], position[1], position[2]));
+
+        let attribute_values = parse_values(line_number, attribute_values.trim_end().trim_end_matches(')'), value_count)?;
+        values.resize_with(value_count, Vec::new);
+        for (column, value) in values.iter_mut().zip(attribute_values) {
+            column.push(value);
+        }
+    }
+
+    let mut time = 0.0;
+    while let Some((_, line)) = lines.next() {
+        if line.trim() != "DetailAttrib" || detail_count == 0 {
+            continue;
+        }
+
+        let details = read_attribute_definitions(&mut lines, detail_count)?;
+        let (line_number, line) = lines.next().ok_or_else(|| ParticleFileError::parse(None, "geo detail values missing"))?;
+        let detail_values = line.trim().trim_start_matches('(').trim_end_matches(')');
+
+        let mut offset = 0;
+        for detail in details {
+            if detail.name == "time" {
+                let value = detail_values.split_whitespace().nth(offset)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| ParticleFileError::parse(line_number, "invalid time value"))?;
+                time = value;
+            }
+            offset += detail.size;
+        }
+        break;
+    }
+
+    let mut frame = ParticleFrame { positions, time, attributes: Vec::new() };
+    let mut columns = values.into_iter();
+    for attribute in attributes {
+        let components = columns.by_ref().take(attribute.size).collect::<Vec<_>>();
+        let name = frame_name(&attribute.name).to_owned();
+
+        frame = match components.as_slice() {
+            [x, y, z] => {
+                let vectors = x.iter().zip(y).zip(z).map(|((x, y), z)| glam::Vec3::new(*x, *y, *z)).collect();
+                frame.with_attribute(&name, AttributeValues::Vector(vectors))
+            }
+            [scalar] => frame.with_attribute(&name, AttributeValues::Scalar(scalar.clone())),
+            // Without points there are no columns
+            [] => frame,
+            components => components.iter()
+                .enumerate()
+                .fold(frame, |frame, (index, component)| {
+                    frame.with_attribute(&format!("{name}_{index}"), AttributeValues::Scalar(component.clone()))
+                }),
+        };
+    }
+
+    Ok(frame)
+}
+
+
+/// A `.geo` file per frame, named like Houdini's `$F4` sequences: `name.0001.geo`
+#[derive(Debug, Clone)]
+pub struct GeoCache {
+    pub directory: PathBuf,
+    pub name: String,
+}
+
+impl GeoCache {
+    pub fn new(directory: impl Into<PathBuf>, name: &str) -> Self {
+        GeoCache { directory: directory.into(), name: name.to_owned() }
+    }
+
+    pub fn frame_path(&self, frame: u32) -> PathBuf {
+        self.directory.join(format!("{}.{frame:04}.geo", self.name))
+    }
+
+    pub fn write_frame(&self, frame_number: u32, frame: &ParticleFrame) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.frame_path(frame_number);
+        write_geo(File::create(&path)?, frame)?;
+
+        Ok(path)
+    }
+
+    pub fn read_frame(&self, frame_number: u32) -> Result<ParticleFrame, ParticleFileError> {
+        read_geo(File::open(self.frame_path(frame_number))?)
+    }
+
+    /// Numbers of the frames in the directory, sorted
+    pub fn frame_numbers(&self) -> io::Result<Vec<u32>> {
+        let prefix = format!("{}.", self.name);
+        let mut frames = std::fs::read_dir(&self.directory)?
+            .filter_map(|entry| {
+                let file_name = entry.ok()?.file_name().into_string().ok()?;
+                let (name, number) = split_frame_number(file_name.strip_suffix(".geo")?)?;
+                (name == prefix).then(|| number.try_into().ok())?
+            })
+            .collect::<Vec<u32>>();
+        frames.sort_unstable();
+
+        Ok(frames)
+    }
+}
@@ -0,0 +1,75 @@
+use crate::CapturedFrame;
+
+
+/// Squared YIQ distance of black and white, the largest there is
+const MAX_DELTA: f32 = 35215.0;
+
+/// Perceived difference of two rgb colors in 0..1, from their distance in YIQ space
+///
+/// Brightness counts about twice as much as the chroma channels, so a change that's
+/// hard to see, like a slightly different shade of blue, stays small.
+pub fn color_delta(a: [u8; 3], b: [u8; 3]) -> f32 {
+    let yiq = |[r, g, b]: [u8; 3]| {
+        let (r, g, b) = (r as f32, g as f32, b as f32);
+        [
+            r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23,
+            r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_9,
+            r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94,
+        ]
+    };
+    let ([y1, i1, q1], [y2, i2, q2]) = (yiq(a), yiq(b));
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / MAX_DELTA).sqrt()
+}
+
+/// Result of comparing two images of the same size, see [`compare_images`]
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    /// Pixels with a [`color_delta`] above the tolerance
+    pub differing_pixels: usize,
+    pub total_pixels: usize,
+    pub max_delta: f32,
+    /// The expected image faded to gray with the differing pixels in red
+    pub image: CapturedFrame,
+}
+
+impl ImageDiff {
+    pub fn differing_fraction(&self) -> f32 {
+        self.differing_pixels as f32 / self.total_pixels.max(1) as f32
+    }
+}
+
+/// Compares the rgb of every pixel, `tolerance` is the largest [`color_delta`] that still counts as equal
+///
+/// Returns `None` when the sizes differ. Alpha is ignored, rendered frames are opaque.
+pub fn compare_images(expected: &CapturedFrame, actual: &CapturedFrame, tolerance: f32) -> Option<ImageDiff> {
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        return None;
+    }
+
+    let mut differing_pixels = 0;
+    let mut max_delta = 0.0_f32;
+    let mut image = Vec::with_capacity(expected.data.len());
+
+    for (expected, actual) in expected.data.chunks_exact(4).zip(actual.data.chunks_exact(4)) {
+        let expected = [expected[0], expected[1], expected[2]];
+        let delta = color_delta(expected, [actual[0], actual[1], actual[2]]);
+        max_delta = max_delta.max(delta);
+
+        if delta > tolerance {
+            differing_pixels += 1;
+            image.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = (color_delta(expected, [0; 3]) * 255.0 * 0.3 + 255.0 * 0.7) as u8;
+            image.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    Some(ImageDiff {
+        differing_pixels,
+        total_pixels: (expected.width * expected.height) as usize,
+        max_delta,
+        image: CapturedFrame::new(expected.width, expected.height, image),
+    })
+}
@@ -1,5 +1,22 @@
 use crate::{Instance, PARTICLE_SIZE};
-use glam::{vec3, Vec3};
+use glam::{vec2, vec3, Vec3};
+use rand::{Rng, SeedableRng};
+
+
+/// Rng used by the scene generators, its raw output is the same on every platform
+///
+/// ChaCha only fixes the bit stream, turning it into floats and ranges is up to `rand`,
+/// so scenes of a seed can change with a `rand` update. `tests/scene_generators.rs`
+/// pins the default scene to catch that.
+pub type SceneRng = rand_chacha::ChaCha8Rng;
+
+/// Seed of the default scene
+pub const DEFAULT_SEED: u64 = 0;
+
+pub fn scene_rng(seed: u64) -> SceneRng {
+    SceneRng::seed_from_u64(seed)
+}
+
 
 pub fn create_square(grid_dimensions: (u32, u32), screen_dimensions: (u32, u32), offset: (f32, f32, f32)) -> Vec<Instance> {
     let width = grid_dimensions.0;
@@ -10,8 +27,8 @@ pub fn create_square(grid_dimensions: (u32, u32), screen_dimensions: (u32, u32),
 
     let instance_offset: (f32, f32) = (screen_dimensions.0 / grid_dimensions.0, screen_dimensions.1 / grid_dimensions.1);
     let offset = (
-        -1.0 * (width - 1) as f32 * instance_offset.0 / 2.0,
-        -1.0 * (height - 1) as f32 * instance_offset.1 / 2.0,
+        -((width - 1) as f32) * instance_offset.0 / 2.0,
+        -((height - 1) as f32) * instance_offset.1 / 2.0,
         offset.2
     );
 
@@ -33,7 +50,8 @@ pub fn create_square(grid_dimensions: (u32, u32), screen_dimensions: (u32, u32),
     .collect::<Vec<_>>()
 }
 
-pub fn create_cube(wiggle_factor: f32, grid_dimensions: (u32, u32, u32), particle_offset: Option<(f32, f32, f32)>, offset: (f32, f32, f32)) -> Vec<Instance> {
+/// Lattice of `grid_dimensions` particles, each jittered by up to `wiggle_factor` particle sizes
+pub fn create_cube(rng: &mut impl Rng, wiggle_factor: f32, grid_dimensions: (u32, u32, u32), particle_offset: Option<(f32, f32, f32)>, offset: (f32, f32, f32)) -> Vec<Instance> {
     let width = grid_dimensions.0;
     let height = grid_dimensions.1;
     let depth = grid_dimensions.2;
@@ -45,22 +63,24 @@ pub fn create_cube(wiggle_factor: f32, grid_dimensions: (u32, u32, u32), particl
     let instance_offset = particle_offset.unwrap_or(instance_offset);
 
     let offset = (
-        -1.0 * (width - 1) as f32 * instance_offset.0 / 2.0,
-        -1.0 * (height - 1) as f32 * instance_offset.1 / 2.0,
+        -((width - 1) as f32) * instance_offset.0 / 2.0,
+        -((height - 1) as f32) * instance_offset.1 / 2.0,
         offset.2
     );
 
-    (0..depth).flat_map(|z| {
-        (0..height).flat_map(move |y| {
-            (0..width).map(move |x| {
+    // Plain loops, the rng can't be shared between nested iterator closures
+    let mut instances = Vec::with_capacity((width * height * depth) as usize);
+    for z in 0..depth {
+        for y in 0..height {
+            for x in 0..width {
                 let b = z as f32 / depth as f32 + 0.1;
                 let w = vec3(
-                    (rand::random::<f32>() - 0.5) * PARTICLE_SIZE * wiggle_factor, 
-                    (rand::random::<f32>() - 0.5) * PARTICLE_SIZE * wiggle_factor, 
-                    (rand::random::<f32>() - 0.5) * PARTICLE_SIZE * wiggle_factor, 
+                    (rng.gen::<f32>() - 0.5) * PARTICLE_SIZE * wiggle_factor, 
+                    (rng.gen::<f32>() - 0.5) * PARTICLE_SIZE * wiggle_factor, 
+                    (rng.gen::<f32>() - 0.5) * PARTICLE_SIZE * wiggle_factor, 
                 );
 
-                Instance {
+                instances.push(Instance {
                     position: vec3(
                         x as f32 * instance_offset.0 + offset.0 + w.x,
                         y as f32 * instance_offset.1 + offset.1 + w.y,
@@ -70,11 +90,12 @@ pub fn create_cube(wiggle_factor: f32, grid_dimensions: (u32, u32, u32), particl
                         y as f32 / height as f32 * b, 
                         0.8 * b,
                     ),
-                }
-            })
-        })
-    })
-    .collect::<Vec<_>>()
+                });
+            }
+        }
+    }
+
+    instances
 }
 
 pub fn create_dense_rect(grid_dimensions: (u32, u32), offset: (f32, f32, f32), particle_radius: Option<f32>, color: Option<Vec3>) -> Vec<Instance> {
@@ -106,3 +127,217 @@ pub fn create_len(len: u32, color: Vec3) -> Vec<Instance> {
         ..Default::default() 
     }).collect()
 }
+
+
+/// Region particles are generated in, described by its signed distance
+#[derive(Debug, Clone, Copy)]
+pub enum Volume {
+    Sphere { center: Vec3, radius: f32 },
+    /// Upright along the y axis
+    Cylinder { center: Vec3, radius: f32, height: f32 },
+    /// Lying in the xz plane
+    Torus { center: Vec3, major_radius: f32, minor_radius: f32 },
+    Box { min: Vec3, max: Vec3 },
+}
+
+impl Volume {
+    /// Negative inside, positive outside
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        match *self {
+            Volume::Sphere { center, radius } => (point - center).length() - radius,
+            Volume::Cylinder { center, radius, height } => {
+                let p = point - center;
+                let radial = vec2(p.x, p.z).length() - radius;
+                let vertical = p.y.abs() - height / 2.0;
+                radial.max(vertical)
+            }
+            Volume::Torus { center, major_radius, minor_radius } => {
+                let p = point - center;
+                let ring = vec2(p.x, p.z).length() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            Volume::Box { min, max } => {
+                let center = (min + max) / 2.0;
+                let q = (point - center).abs() - (max - min) / 2.0;
+                q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+            }
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.signed_distance(point) <= 0.0
+    }
+
+    /// Axis aligned box around the volume
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        match *self {
+            Volume::Sphere { center, radius } => (center - radius, center + radius),
+            Volume::Cylinder { center, radius, height } => {
+                let extent = vec3(radius, height / 2.0, radius);
+                (center - extent, center + extent)
+            }
+            Volume::Torus { center, major_radius, minor_radius } => {
+                let extent = vec3(major_radius + minor_radius, minor_radius, major_radius + minor_radius);
+                (center - extent, center + extent)
+            }
+            Volume::Box { min, max } => (min, max),
+        }
+    }
+}
+
+/// Calls `f` for every point of a `spacing` lattice covering `volume`, none if the spacing isn't positive
+fn for_each_lattice_point(volume: &Volume, spacing: f32, mut f: impl FnMut(Vec3)) {
+    if spacing.is_nan() || spacing <= 0.0 {
+        return;
+    }
+
+    let (min, max) = volume.bounds();
+    let counts = ((max - min) / spacing).floor().as_uvec3() + 1;
+    // Centers the lattice in the bounds so symmetric volumes get symmetric particles
+    let start = min + ((max - min) - (counts - 1).as_vec3() * spacing) / 2.0;
+
+    for z in 0..counts.z {
+        for y in 0..counts.y {
+            for x in 0..counts.x {
+                f(start + vec3(x as f32, y as f32, z as f32) * spacing);
+            }
+        }
+    }
+}
+
+/// Fills `volume` with particles on a lattice of `spacing`
+pub fn create_volume(volume: Volume, spacing: f32, color: Vec3) -> Vec<Instance> {
+    let mut instances = Vec::new();
+    for_each_lattice_point(&volume, spacing, |position| {
+        if volume.contains(position) {
+            instances.push(Instance { position, color });
+        }
+    });
+
+    instances
+}
+
+/// Only the outermost `layers` lattice layers of `volume`, for boundary particles
+pub fn create_shell(volume: Volume, spacing: f32, layers: u32, color: Vec3) -> Vec<Instance> {
+    let thickness = layers as f32 * spacing;
+
+    let mut instances = Vec::new();
+    for_each_lattice_point(&volume, spacing, |position| {
+        let distance = volume.signed_distance(position);
+        if distance <= 0.0 && distance > -thickness {
+            instances.push(Instance { position, color });
+        }
+    });
+
+    instances
+}
+
+pub fn create_sphere(center: Vec3, radius: f32, spacing: f32, color: Vec3) -> Vec<Instance> {
+    create_volume(Volume::Sphere { center, radius }, spacing, color)
+}
+
+pub fn create_cylinder(center: Vec3, radius: f32, height: f32, spacing: f32, color: Vec3) -> Vec<Instance> {
+    create_volume(Volume::Cylinder { center, radius, height }, spacing, color)
+}
+
+pub fn create_torus(center: Vec3, major_radius: f32, minor_radius: f32, spacing: f32, color: Vec3) -> Vec<Instance> {
+    create_volume(Volume::Torus { center, major_radius, minor_radius }, spacing, color)
+}
+
+/// Random particles in `volume` no closer than `spacing` to each other (Bridson's algorithm)
+///
+/// Unlike the lattice generators this leaves no grid artifacts, so fluids start relaxed.
+/// A spacing that isn't positive gives no particles, it would need infinitely many, and
+/// neither does one so small that the acceleration grid can't be indexed.
+pub fn create_poisson_disk(rng: &mut impl Rng, volume: Volume, spacing: f32, color: Vec3) -> Vec<Instance> {
+    // Attempts around an active sample before it's retired
+    const ATTEMPTS: u32 = 30;
+
+    if spacing.is_nan() || spacing <= 0.0 {
+        return Vec::new();
+    }
+
+    let (min, max) = volume.bounds();
+    // At most one sample fits in a cell of this size
+    let cell_size = spacing / 3f32.sqrt();
+    let dimensions = ((max - min) / cell_size).ceil().as_uvec3().max(glam::UVec3::ONE);
+    let cell_of = |point: Vec3| ((point - min) / cell_size).as_uvec3().min(dimensions - 1);
+    let [width, height, depth] = dimensions.to_array().map(|dimension| dimension as usize);
+    // Below the cell count, so it can't overflow once the count didn't
+    let cell_index = |cell: glam::UVec3| cell.x as usize + width * (cell.y as usize + height * cell.z as usize);
+
+    // A tiny spacing in a big volume has more cells than can be addressed
+    let Some(cell_count) = width.checked_mul(height).and_then(|area| area.checked_mul(depth)) else {
+        return Vec::new();
+    };
+    let mut grid: Vec<Option<usize>> = vec![None; cell_count];
+    let mut samples: Vec<Vec3> = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+
+    let fits = |point: Vec3, samples: &[Vec3], grid: &[Option<usize>]| {
+        if point.cmplt(min).any() || point.cmpgt(max).any() || !volume.contains(point) {
+            return false;
+        }
+
+        let cell = cell_of(point).as_ivec3();
+        let reach = 2;
+        for z in (cell.z - reach).max(0)..=(cell.z + reach).min(dimensions.z as i32 - 1) {
+            for y in (cell.y - reach).max(0)..=(cell.y + reach).min(dimensions.y as i32 - 1) {
+                for x in (cell.x - reach).max(0)..=(cell.x + reach).min(dimensions.x as i32 - 1) {
+                    let neighbor = grid[cell_index(glam::uvec3(x as u32, y as u32, z as u32))];
+                    if neighbor.is_some_and(|neighbor| samples[neighbor].distance_squared(point) < spacing * spacing) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    };
+
+    // Volumes like a torus don't contain their center, so the first sample is searched for
+    let first = (0..1000)
+        .map(|_| min + vec3(rng.gen(), rng.gen(), rng.gen()) * (max - min))
+        .find(|point| volume.contains(*point));
+    let Some(first) = first else {
+        return Vec::new();
+    };
+
+    grid[cell_index(cell_of(first))] = Some(0);
+    samples.push(first);
+    active.push(0);
+
+    while !active.is_empty() {
+        let active_index = rng.gen_range(0..active.len());
+        let center = samples[active[active_index]];
+
+        let candidate = (0..ATTEMPTS)
+            .map(|_| {
+                // Uniform direction, distance between one and two spacings
+                let direction = loop {
+                    let direction = vec3(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0));
+                    let length_squared = direction.length_squared();
+                    if length_squared > 1e-6 && length_squared <= 1.0 {
+                        break direction / length_squared.sqrt();
+                    }
+                };
+                center + direction * spacing * rng.gen_range(1.0..2.0)
+            })
+            .find(|candidate| fits(*candidate, &samples, &grid));
+
+        match candidate {
+            Some(candidate) => {
+                grid[cell_index(cell_of(candidate))] = Some(samples.len());
+                active.push(samples.len());
+                samples.push(candidate);
+            }
+            None => {
+                active.swap_remove(active_index);
+            }
+        }
+    }
+
+    samples.into_iter()
+        .map(|position| Instance { position, color })
+        .collect()
+}
@@ -0,0 +1,266 @@
+use std::{collections::HashSet, fmt, path::{Path, PathBuf}};
+use glam::{vec3, IVec3, Mat4, UVec3, Vec3};
+use crate::Instance;
+
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io { path: PathBuf, error: std::io::Error },
+    Obj { path: PathBuf, error: tobj::LoadError },
+    /// Only `.obj` and `.stl` files are supported
+    UnsupportedFormat(PathBuf),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, error } => write!(f, "failed to read mesh {}: {error}", path.display()),
+            Self::Obj { path, error } => write!(f, "failed to load obj {}: {error}", path.display()),
+            Self::UnsupportedFormat(path) => write!(f, "unsupported mesh format {}, expected .obj or .stl", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Obj { error, .. } => Some(error),
+            Self::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+
+/// Indexed triangles with per vertex normals
+#[derive(Debug, Clone, Default)]
+pub struct TriangleMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl TriangleMesh {
+    /// Loads an `.obj` or `.stl` file depending on its extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MeshError> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("obj") => Self::load_obj(path),
+            Some("stl") => Self::load_stl(path),
+            _ => Err(MeshError::UnsupportedFormat(path.to_owned())),
+        }
+    }
+
+    /// Merges every model of the file, normals are computed when the file has none
+    pub fn load_obj(path: impl AsRef<Path>) -> Result<Self, MeshError> {
+        let path = path.as_ref();
+        let options = tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        };
+        let (models, _materials) = tobj::load_obj(path, &options)
+            .map_err(|error| MeshError::Obj { path: path.to_owned(), error })?;
+
+        let mut mesh = TriangleMesh::default();
+        let mut has_normals = true;
+        for model in models {
+            let offset = mesh.positions.len() as u32;
+            let positions = model.mesh.positions.chunks_exact(3).map(|p| vec3(p[0], p[1], p[2]));
+            let vertex_count = positions.len();
+
+            mesh.positions.extend(positions);
+            mesh.indices.extend(model.mesh.indices.iter().map(|index| index + offset));
+
+            has_normals &= model.mesh.normals.len() == vertex_count * 3;
+            mesh.normals.extend(model.mesh.normals.chunks_exact(3).map(|n| vec3(n[0], n[1], n[2])));
+        }
+
+        if !has_normals {
+            mesh.compute_normals();
+        }
+
+        Ok(mesh)
+    }
+
+    /// Binary or ascii stl, normals are computed from the faces
+    pub fn load_stl(path: impl AsRef<Path>) -> Result<Self, MeshError> {
+        let path = path.as_ref();
+        let io_error = |error| MeshError::Io { path: path.to_owned(), error };
+
+        let mut file = std::fs::File::open(path).map_err(io_error)?;
+        let stl = stl_io::read_stl(&mut file).map_err(io_error)?;
+
+        let mut mesh = TriangleMesh {
+            positions: stl.vertices.iter().map(|vertex| Vec3::from(vertex.0)).collect(),
+            normals: Vec::new(),
+            indices: stl.faces.iter().flat_map(|face| face.vertices.map(|index| index as u32)).collect(),
+        };
+        mesh.compute_normals();
+
+        Ok(mesh)
+    }
+
+    /// Area weighted vertex normals
+    pub fn compute_normals(&mut self) {
+        self.normals = vec![Vec3::ZERO; self.positions.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| self.positions[triangle[corner] as usize]);
+            let normal = (b - a).cross(c - a);
+
+            for index in triangle {
+                self.normals[*index as usize] += normal;
+            }
+        }
+
+        for normal in self.normals.iter_mut() {
+            *normal = normal.normalize_or_zero();
+        }
+    }
+
+    pub fn transformed(&self, transform: Mat4) -> Self {
+        let normal_transform = transform.inverse().transpose();
+
+        TriangleMesh {
+            positions: self.positions.iter().map(|position| transform.transform_point3(*position)).collect(),
+            normals: self.normals.iter().map(|normal| normal_transform.transform_vector3(*normal).normalize_or_zero()).collect(),
+            indices: self.indices.clone(),
+        }
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.indices.chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| self.positions[triangle[corner] as usize]))
+    }
+
+    /// Axis aligned box around every vertex
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        self.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), position| (min.min(*position), max.max(*position)),
+        )
+    }
+
+    /// Offset that keeps rays from running exactly through an edge shared by two triangles
+    fn ray_nudge(min: Vec3, max: Vec3) -> f32 {
+        (max - min).max_element() * 1e-5
+    }
+
+    /// X coordinates where the ray from `(-inf, y, z)` along +x crosses the surface, sorted
+    ///
+    /// `nudge` comes from [`TriangleMesh::ray_nudge`], it's passed in so scanning many
+    /// rows doesn't go over every vertex for the bounds each time.
+    fn crossings(&self, y: f32, z: f32, nudge: f32) -> Vec<f32> {
+        let (y, z) = (y + nudge, z + nudge * 0.37);
+
+        let mut crossings = self.triangles()
+            .filter_map(|[a, b, c]| {
+                // Point in triangle on the yz plane, then the x of that point on the triangle
+                let edge = |p: Vec3, q: Vec3| (q.y - p.y) * (z - p.z) - (q.z - p.z) * (y - p.y);
+                let (ab, bc, ca) = (edge(a, b), edge(b, c), edge(c, a));
+                let inside = (ab >= 0.0 && bc >= 0.0 && ca >= 0.0) || (ab <= 0.0 && bc <= 0.0 && ca <= 0.0);
+                let area = ab + bc + ca;
+                if !inside || area == 0.0 {
+                    return None;
+                }
+
+                Some((bc * a.x + ca * b.x + ab * c.x) / area)
+            })
+            .collect::<Vec<_>>();
+
+        crossings.sort_by(f32::total_cmp);
+        crossings
+    }
+
+    /// Even-odd test, the mesh has to be closed
+    pub fn contains(&self, point: Vec3) -> bool {
+        let (min, max) = self.bounds();
+        let crossings = self.crossings(point.y, point.z, Self::ray_nudge(min, max));
+
+        crossings.iter().filter(|x| **x < point.x).count() % 2 == 1
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshSampling {
+    /// Lattice points inside the mesh, for fluid blocks, the mesh has to be closed
+    Interior,
+    /// Points on the triangles, for boundary particles, works on open meshes like a cup
+    Surface,
+}
+
+/// Particles `spacing` apart filling or covering `mesh`, none if the spacing isn't positive
+pub fn create_from_mesh(mesh: &TriangleMesh, sampling: MeshSampling, spacing: f32, color: Vec3) -> Vec<Instance> {
+    if spacing.is_nan() || spacing <= 0.0 {
+        return Vec::new();
+    }
+
+    let positions = match sampling {
+        MeshSampling::Interior => sample_interior(mesh, spacing),
+        MeshSampling::Surface => sample_surface(mesh, spacing),
+    };
+
+    positions.into_iter()
+        .map(|position| Instance { position, color })
+        .collect()
+}
+
+/// Scanline voxelization, one ray per row of voxels instead of one per voxel
+///
+/// Particles sit at the voxel centers, so none of them end up on the surface.
+fn sample_interior(mesh: &TriangleMesh, spacing: f32) -> Vec<Vec3> {
+    let (min, max) = mesh.bounds();
+    let counts = ((max - min) / spacing).ceil().as_uvec3().max(UVec3::ONE);
+    let start = min + ((max - min) - (counts - 1).as_vec3() * spacing) / 2.0;
+    let nudge = TriangleMesh::ray_nudge(min, max);
+
+    let mut positions = Vec::new();
+    for z in 0..counts.z {
+        for y in 0..counts.y {
+            let row = start + vec3(0.0, y as f32, z as f32) * spacing;
+            let crossings = mesh.crossings(row.y, row.z, nudge);
+
+            for pair in crossings.chunks_exact(2) {
+                for x in 0..counts.x {
+                    let position = row + Vec3::X * x as f32 * spacing;
+                    if position.x > pair[0] && position.x < pair[1] {
+                        positions.push(position);
+                    }
+                }
+            }
+        }
+    }
+
+    positions
+}
+
+/// Barycentric grid on every triangle, thinned to one point per `spacing` cell
+fn sample_surface(mesh: &TriangleMesh, spacing: f32) -> Vec<Vec3> {
+    let mut occupied = HashSet::new();
+    let mut positions = Vec::new();
+
+    for [a, b, c] in mesh.triangles() {
+        let longest = (b - a).length().max((c - b).length()).max((a - c).length());
+        let steps = (longest / spacing).ceil().max(1.0) as u32;
+
+        for i in 0..=steps {
+            for j in 0..=steps - i {
+                let (u, v) = (i as f32 / steps as f32, j as f32 / steps as f32);
+                let position = a + (b - a) * u + (c - a) * v;
+
+                let cell: IVec3 = (position / spacing).round().as_ivec3();
+                if occupied.insert(cell) {
+                    positions.push(position);
+                }
+            }
+        }
+    }
+
+    positions
+}
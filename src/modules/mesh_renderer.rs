@@ -0,0 +1,375 @@
+use std::path::Path;
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+use crate::{
+    State, RenderNode, GraphResources, RendererError,
+    TriangleMesh, MeshError, DepthTexture,
+    HDR_FORMAT, HDR, DEPTH, MSAA,
+};
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+}
+
+impl MeshVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+            ],
+        }
+    }
+}
+
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshShading {
+    /// Diffuse only
+    Lambert = 0,
+    /// Diffuse with a blinn-phong highlight
+    Phong = 1,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MeshMaterial {
+    pub color: Vec3,
+    pub shading: MeshShading,
+    /// Strength of the highlight, only used by [`MeshShading::Phong`]
+    pub specular: f32,
+    pub shininess: f32,
+}
+
+impl Default for MeshMaterial {
+    fn default() -> Self {
+        MeshMaterial {
+            color: Vec3::splat(0.6),
+            shading: MeshShading::Phong,
+            specular: 0.3,
+            shininess: 32.0,
+        }
+    }
+}
+
+/// A single directional light shared by every mesh
+#[derive(Debug, Clone, Copy)]
+pub struct MeshLighting {
+    /// Points towards the light
+    pub direction: Vec3,
+    pub color: Vec3,
+    /// Fraction of the light color reaching surfaces facing away from the light
+    pub ambient: f32,
+}
+
+impl Default for MeshLighting {
+    fn default() -> Self {
+        MeshLighting {
+            direction: Vec3::new(0.4, 1.0, 0.3).normalize(),
+            color: Vec3::ONE,
+            ambient: 0.15,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightingUniform {
+    direction: [f32; 4],
+    color: [f32; 4],
+    eye: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct MeshUniform {
+    model: [[f32; 4]; 4],
+    normal: [[f32; 4]; 4],
+    color: [f32; 4],
+    specular: f32,
+    shininess: f32,
+    shading: u32,
+    _padding: u32,
+}
+
+impl MeshUniform {
+    fn new(transform: Mat4, material: &MeshMaterial) -> Self {
+        MeshUniform {
+            model: transform.to_cols_array_2d(),
+            normal: transform.inverse().transpose().to_cols_array_2d(),
+            color: material.color.extend(1.0).into(),
+            specular: material.specular,
+            shininess: material.shininess,
+            shading: material.shading as u32,
+            _padding: 0,
+        }
+    }
+}
+
+
+/// A mesh uploaded to the gpu, `transform` and `material` can be changed between frames
+pub struct SceneMesh {
+    pub transform: Mat4,
+    pub material: MeshMaterial,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_count: u32,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+
+/// Draws static scene geometry like tanks, obstacles and colliders into the hdr and depth targets
+///
+/// Doesn't clear the targets, so it has to be added after [`crate::ParticleNode`],
+/// which [`State::add_render_node`] does by appending to the graph.
+pub struct MeshNode {
+    pub meshes: Vec<SceneMesh>,
+    pub lighting: MeshLighting,
+    pipeline: wgpu::RenderPipeline,
+    lighting_buffer: wgpu::Buffer,
+    globals_bind_group: wgpu::BindGroup,
+    mesh_bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl MeshNode {
+    pub fn new(state: &State) -> Result<Self, RendererError> {
+        let device = &state.device;
+
+        let mut preprocessor = crate::shader_preprocessor();
+        preprocessor.add_source("shaders/camera.wgsl", include_str!("../shaders/camera.wgsl"));
+        let processed = preprocessor.process_str("shaders/mesh.wgsl", include_str!("../shaders/mesh.wgsl"))?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mesh Shader"),
+            source: wgpu::ShaderSource::Wgsl(processed.source.into()),
+        });
+
+        let lighting_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Mesh Lighting Buffer"),
+            size: std::mem::size_of::<LightingUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let globals_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry(0, wgpu::ShaderStages::VERTEX),
+                uniform_entry(1, wgpu::ShaderStages::FRAGMENT),
+            ],
+            label: Some("Mesh globals bind group layout"),
+        });
+
+        let mesh_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[uniform_entry(0, wgpu::ShaderStages::VERTEX_FRAGMENT)],
+            label: Some("Mesh bind group layout"),
+        });
+
+        let globals_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &globals_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: state.camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: lighting_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("mesh_globals_bind_group"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mesh Pipeline Layout"),
+            bind_group_layouts: &[&globals_bind_group_layout, &mesh_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mesh Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_mesh",
+                buffers: &[MeshVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_mesh",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HDR_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                front_face: wgpu::FrontFace::Ccw,
+                // Open meshes are seen from both sides
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                bias: wgpu::DepthBiasState::default(),
+                stencil: wgpu::StencilState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: state.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Ok(MeshNode {
+            meshes: Vec::new(),
+            lighting: MeshLighting::default(),
+            pipeline,
+            lighting_buffer,
+            globals_bind_group,
+            mesh_bind_group_layout,
+        })
+    }
+
+    /// Uploads `mesh` and returns its index in `meshes`
+    pub fn add_mesh(&mut self, device: &wgpu::Device, mesh: &TriangleMesh, transform: Mat4, material: MeshMaterial) -> usize {
+        let vertices = mesh.positions.iter()
+            .zip(mesh.normals.iter())
+            .map(|(position, normal)| MeshVertex {
+                position: position.to_array(),
+                normal: normal.to_array(),
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Index Buffer"),
+            contents: bytemuck::cast_slice(&mesh.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Mesh Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[MeshUniform::new(transform, &material)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.mesh_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("mesh_bind_group"),
+        });
+
+        self.meshes.push(SceneMesh {
+            transform,
+            material,
+            vertex_buffer,
+            index_buffer,
+            index_count: mesh.indices.len() as u32,
+            uniform_buffer,
+            bind_group,
+        });
+
+        self.meshes.len() - 1
+    }
+
+    /// Loads an `.obj` or `.stl` file and adds it, see [`TriangleMesh::load`]
+    pub fn load(&mut self, device: &wgpu::Device, path: impl AsRef<Path>, transform: Mat4, material: MeshMaterial) -> Result<usize, MeshError> {
+        let mesh = TriangleMesh::load(path)?;
+        Ok(self.add_mesh(device, &mesh, transform, material))
+    }
+}
+
+impl RenderNode for MeshNode {
+    fn label(&self) -> &str {
+        "meshes"
+    }
+
+    fn writes(&self) -> Vec<String> {
+        vec![HDR.to_owned(), DEPTH.to_owned()]
+    }
+
+    fn record(&mut self, state: &State, resources: &GraphResources, encoder: &mut wgpu::CommandEncoder) {
+        if self.meshes.is_empty() {
+            return;
+        }
+
+        let lighting = LightingUniform {
+            direction: self.lighting.direction.extend(0.0).into(),
+            color: self.lighting.color.extend(self.lighting.ambient).into(),
+            eye: Vec3::from(state.camera.eye).extend(1.0).into(),
+        };
+        state.queue.write_buffer(&self.lighting_buffer, 0, bytemuck::cast_slice(&[lighting]));
+        for mesh in &self.meshes {
+            let uniform = MeshUniform::new(mesh.transform, &mesh.material);
+            state.queue.write_buffer(&mesh.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+
+        let hdr_view = resources.view(HDR);
+        let msaa_view = resources.target(MSAA).map(|msaa| &msaa.view);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Mesh Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_view.unwrap_or(hdr_view),
+                resolve_target: msaa_view.map(|_| hdr_view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: resources.view(DEPTH),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.globals_bind_group, &[]);
+        for mesh in &self.meshes {
+            render_pass.set_bind_group(1, &mesh.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..mesh.index_count, 0, 0..1);
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use std::{borrow::Cow, collections::HashMap, fmt, fs::File, io, path::{Path, PathBuf}};
+use glam::Vec3;
+use crate::{
+    Instance, Solver, ParticleSnapshot,
+    VtkFormat, PlyFormat, save_vtk, read_ply, write_ply, read_csv, write_csv, read_geo, write_geo,
+};
+
+
+#[derive(Debug)]
+pub enum ParticleFileError {
+    Io(io::Error),
+    /// The file doesn't follow its format, `line` is 1-based where known
+    Parse { line: Option<usize>, message: String },
+    /// There are no `x`, `y` and `z` columns to take the positions from
+    MissingPositions,
+    UnsupportedFormat(PathBuf),
+}
+
+impl ParticleFileError {
+    pub(crate) fn parse(line: impl Into<Option<usize>>, message: impl Into<String>) -> Self {
+        Self::Parse { line: line.into(), message: message.into() }
+    }
+}
+
+impl fmt::Display for ParticleFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Parse { line: Some(line), message } => write!(f, "line {line}: {message}"),
+            Self::Parse { line: None, message } => write!(f, "{message}"),
+            Self::MissingPositions => write!(f, "particle file has no x, y and z columns"),
+            Self::UnsupportedFormat(path) => write!(f, "unsupported particle file {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ParticleFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParticleFileError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Most items reserved up front for a count read from a file header
+const MAX_HEADER_RESERVE: usize = 1 << 16;
+
+/// Empty vec for `count` items announced by a file header
+///
+/// Header counts are untrusted, so the reservation is capped. A corrupt count runs into
+/// the end of the file instead of exhausting memory, the vec grows past the cap as usual.
+pub(crate) fn with_header_capacity<T>(count: usize) -> Vec<T> {
+    Vec::with_capacity(count.min(MAX_HEADER_RESERVE))
+}
+
+/// Attribute name as one token of the text formats, whitespace and commas separate them
+pub(crate) fn attribute_token(name: &str) -> Cow<'_, str> {
+    let is_separator = |c: char| c.is_whitespace() || c == ',';
+
+    match name {
+        "" => Cow::Borrowed("unnamed"),
+        name if name.contains(is_separator) => Cow::Owned(name.replace(is_separator, "_")),
+        name => Cow::Borrowed(name),
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValues {
+    Scalar(Vec<f32>),
+    Vector(Vec<Vec3>),
+}
+
+impl AttributeValues {
+    pub fn len(&self) -> usize {
+        match self {
+            AttributeValues::Scalar(values) => values.len(),
+            AttributeValues::Vector(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn components(&self) -> usize {
+        match self {
+            AttributeValues::Scalar(_) => 1,
+            AttributeValues::Vector(_) => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticleAttribute {
+    pub name: String,
+    pub values: AttributeValues,
+}
+
+
+/// Positions and per particle attributes at one point in time, the common ground of the file formats
+///
+/// Attributes have one value per position. [`ParticleFrame::from_instances`] and
+/// [`ParticleFrame::with_solver`] fill in `color`, `velocity`, `density` and `pressure`,
+/// anything else can be added with [`ParticleFrame::with_attribute`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParticleFrame {
+    pub positions: Vec<Vec3>,
+    /// Simulated time in seconds
+    pub time: f32,
+    pub attributes: Vec<ParticleAttribute>,
+}
+
+impl ParticleFrame {
+    pub const COLOR: &'static str = "color";
+    pub const VELOCITY: &'static str = "velocity";
+    pub const DENSITY: &'static str = "density";
+    pub const PRESSURE: &'static str = "pressure";
+    /// Identifies a particle across frames, see [`ParticleFrame::lerp`]
+    pub const ID: &'static str = "id";
+
+    pub fn from_instances(instances: &[Instance]) -> Self {
+        ParticleFrame {
+            positions: instances.iter().map(|instance| instance.position).collect(),
+            time: 0.0,
+            attributes: Vec::new(),
+        }
+        .with_attribute(Self::COLOR, AttributeValues::Vector(instances.iter().map(|instance| instance.color).collect()))
+    }
+
+    /// Adds the solver's per particle attributes and time, they have to be indexed like the positions
+    pub fn with_solver(mut self, solver: &Solver) -> Self {
+        self.time = solver.time;
+        self.with_attribute(Self::VELOCITY, AttributeValues::Vector(solver.velocities.clone()))
+            .with_attribute(Self::DENSITY, AttributeValues::Scalar(solver.densities.clone()))
+            .with_attribute(Self::PRESSURE, AttributeValues::Scalar(solver.pressures.clone()))
+    }
+
+    /// Adds or replaces the attribute `name`, values that don't match the particle count are ignored
+    pub fn with_attribute(mut self, name: &str, values: AttributeValues) -> Self {
+        if values.len() != self.positions.len() {
+            log::warn!("attribute `{name}` has {} values for {} particles, skipping it", values.len(), self.positions.len());
+            return self;
+        }
+
+        match self.attributes.iter_mut().find(|attribute| attribute.name == name) {
+            Some(attribute) => attribute.values = values,
+            None => self.attributes.push(ParticleAttribute { name: name.to_owned(), values }),
+        }
+
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&AttributeValues> {
+        self.attributes.iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| &attribute.values)
+    }
+
+    pub fn scalar(&self, name: &str) -> Option<&[f32]> {
+        match self.attribute(name)? {
+            AttributeValues::Scalar(values) => Some(values),
+            AttributeValues::Vector(_) => None,
+        }
+    }
+
+    pub fn vector(&self, name: &str) -> Option<&[Vec3]> {
+        match self.attribute(name)? {
+            AttributeValues::Vector(values) => Some(values),
+            AttributeValues::Scalar(_) => None,
+        }
+    }
+
+    /// Instances at the positions, white where the frame has no colors
+    pub fn to_instances(&self) -> Vec<Instance> {
+        let colors = self.vector(Self::COLOR);
+
+        self.positions.iter()
+            .enumerate()
+            .map(|(index, position)| Instance {
+                position: *position,
+                color: colors.map_or(Vec3::ONE, |colors| colors[index]),
+            })
+            .collect()
+    }
+
+    /// Blends positions, time and the attributes both frames have `t` of the way to `other`
+    ///
+    /// When both frames have an [`ParticleFrame::ID`] attribute particles are matched by it,
+    /// particles without a partner in `other` keep their values. Otherwise they're matched by
+    /// index, and frames with a different particle count are returned as is.
+    pub fn lerp(&self, other: &ParticleFrame, t: f32) -> ParticleFrame {
+        // Index in `other` of every particle
+        let partners: Vec<Option<usize>> = match (self.scalar(Self::ID), other.scalar(Self::ID)) {
+            (Some(ids), Some(other_ids)) => {
+                let indices = other_ids.iter()
+                    .enumerate()
+                    .map(|(index, id)| (id.to_bits(), index))
+                    .collect::<HashMap<_, _>>();
+                ids.iter().map(|id| indices.get(&id.to_bits()).copied()).collect()
+            }
+            _ if self.len() == other.len() => (0..self.len()).map(Some).collect(),
+            _ => return self.clone(),
+        };
+
+        let attributes = self.attributes.iter()
+            .map(|attribute| {
+                let values = match (&attribute.values, other.attribute(&attribute.name)) {
+                    (AttributeValues::Scalar(from), Some(AttributeValues::Scalar(to))) => AttributeValues::Scalar(
+                        blend(from, to, &partners, |from, to| from + (to - from) * t),
+                    ),
+                    (AttributeValues::Vector(from), Some(AttributeValues::Vector(to))) => AttributeValues::Vector(
+                        blend(from, to, &partners, |from, to| from.lerp(to, t)),
+                    ),
+                    (values, _) => values.clone(),
+                };
+                ParticleAttribute { name: attribute.name.clone(), values }
+            })
+            .collect();
+
+        ParticleFrame {
+            positions: blend(&self.positions, &other.positions, &partners, |from, to| from.lerp(to, t)),
+            time: self.time + (other.time - self.time) * t,
+            attributes,
+        }
+    }
+
+    /// Copies the time and solver attributes the frame has into `solver`, the rest is reset
+    pub fn restore_solver(&self, solver: &mut Solver) {
+        solver.reset(self.len());
+        solver.time = self.time;
+
+        if let Some(velocities) = self.vector(Self::VELOCITY) {
+            solver.velocities = velocities.to_vec();
+        }
+        if let Some(densities) = self.scalar(Self::DENSITY) {
+            solver.densities = densities.to_vec();
+        }
+        if let Some(pressures) = self.scalar(Self::PRESSURE) {
+            solver.pressures = pressures.to_vec();
+        }
+    }
+}
+
+impl ParticleFrame {
+    /// Every component as a named column: `x y z` for the positions, `red green blue`
+    /// for the color, `name_x name_y name_z` for other vectors and `name` for scalars
+    pub fn columns(&self) -> Vec<(String, Vec<f32>)> {
+        let component = |values: &[Vec3], axis: usize| values.iter().map(|value| value[axis]).collect::<Vec<_>>();
+        let mut columns = ["x", "y", "z"].into_iter()
+            .enumerate()
+            .map(|(axis, name)| (name.to_owned(), component(&self.positions, axis)))
+            .collect::<Vec<_>>();
+
+        for attribute in &self.attributes {
+            match &attribute.values {
+                AttributeValues::Scalar(values) => columns.push((attribute.name.clone(), values.clone())),
+                AttributeValues::Vector(values) => {
+                    for (axis, suffix) in ["x", "y", "z"].into_iter().enumerate() {
+                        let name = match attribute.name == Self::COLOR {
+                            true => ["red", "green", "blue"][axis].to_owned(),
+                            false => format!("{}_{suffix}", attribute.name),
+                        };
+                        columns.push((name, component(values, axis)));
+                    }
+                }
+            }
+        }
+
+        columns
+    }
+
+    /// Inverse of [`ParticleFrame::columns`], columns that don't form a vector become scalars
+    pub fn from_columns(mut columns: Vec<(String, Vec<f32>)>) -> Result<Self, ParticleFileError> {
+        let positions = take_vector(&mut columns, ["x", "y", "z"]).ok_or(ParticleFileError::MissingPositions)?;
+        let mut frame = ParticleFrame { positions, ..Default::default() };
+
+        if let Some(colors) = take_vector(&mut columns, ["red", "green", "blue"]) {
+            frame = frame.with_attribute(Self::COLOR, AttributeValues::Vector(colors));
+        }
+
+        let vector_names = columns.iter()
+            .filter_map(|(column, _)| column.strip_suffix("_x"))
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        for name in vector_names {
+            let [x, y, z] = ["x", "y", "z"].map(|suffix| format!("{name}_{suffix}"));
+            if let Some(values) = take_vector(&mut columns, [&x, &y, &z]) {
+                frame = frame.with_attribute(&name, AttributeValues::Vector(values));
+            }
+        }
+
+        for (name, values) in columns {
+            frame = frame.with_attribute(&name, AttributeValues::Scalar(values));
+        }
+
+        Ok(frame)
+    }
+
+    /// Reads a `.ply`, `.csv` or houdini `.geo` file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ParticleFileError> {
+        let path = path.as_ref();
+
+        match extension(path).as_deref() {
+            Some("ply") => read_ply(File::open(path)?),
+            Some("csv") => read_csv(File::open(path)?),
+            Some("geo") => read_geo(File::open(path)?),
+            _ => Err(ParticleFileError::UnsupportedFormat(path.to_owned())),
+        }
+    }
+
+    /// Writes a binary `.ply`, `.csv`, houdini `.geo`, `.vtk` or `.vtu` file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ParticleFileError> {
+        let path = path.as_ref();
+
+        match extension(path).as_deref() {
+            Some("ply") => write_ply(File::create(path)?, self, PlyFormat::BinaryLittleEndian)?,
+            Some("csv") => write_csv(File::create(path)?, self)?,
+            Some("geo") => write_geo(File::create(path)?, self)?,
+            Some("vtk") => save_vtk(path, self, VtkFormat::Legacy)?,
+            Some("vtu") => save_vtk(path, self, VtkFormat::Xml)?,
+            _ => return Err(ParticleFileError::UnsupportedFormat(path.to_owned())),
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes and zips the three columns, only when all of them exist so a lone `name_x` stays a scalar
+fn take_vector(columns: &mut Vec<(String, Vec<f32>)>, names: [&str; 3]) -> Option<Vec<Vec3>> {
+    let position = |columns: &[(String, Vec<f32>)], name: &str| columns.iter().position(|(column, _)| column == name);
+    if names.iter().any(|name| position(columns, name).is_none()) {
+        return None;
+    }
+
+    let [x, y, z] = names.map(|name| columns.remove(position(columns, name).unwrap_or_default()).1);
+
+    Some(x.into_iter().zip(y).zip(z).map(|((x, y), z)| Vec3::new(x, y, z)).collect())
+}
+
+/// `from` blended towards the value of each partner in `to`, unmatched values are copied
+fn blend<T: Copy>(from: &[T], to: &[T], partners: &[Option<usize>], f: impl Fn(T, T) -> T) -> Vec<T> {
+    from.iter()
+        .zip(partners)
+        .map(|(from, partner)| match partner {
+            Some(index) => f(*from, to[*index]),
+            None => *from,
+        })
+        .collect()
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+impl From<&ParticleSnapshot> for ParticleFrame {
+    fn from(snapshot: &ParticleSnapshot) -> Self {
+        let mut frame = ParticleFrame::from_instances(&snapshot.instances)
+            .with_attribute(Self::VELOCITY, AttributeValues::Vector(snapshot.velocities.clone()))
+            .with_attribute(Self::DENSITY, AttributeValues::Scalar(snapshot.densities.clone()))
+            .with_attribute(Self::PRESSURE, AttributeValues::Scalar(snapshot.pressures.clone()));
+        frame.time = snapshot.time;
+
+        frame
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::VecDeque, time::Instant};
+use glam::Vec3;
+use crate::{Instance, Solver};
+
+
+/// Speed multipliers offered by the ui and keyboard shortcuts
+pub const PLAYBACK_SPEEDS: [f32; 7] = [0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// Particles and solver attributes at one point in time
+#[derive(Debug, Clone)]
+pub struct ParticleSnapshot {
+    pub instances: Vec<Instance>,
+    pub velocities: Vec<Vec3>,
+    pub densities: Vec<f32>,
+    pub pressures: Vec<f32>,
+    pub time: f32,
+    pub steps: u64,
+}
+
+impl ParticleSnapshot {
+    pub fn capture(instances: &[Instance], solver: &Solver) -> Self {
+        ParticleSnapshot {
+            instances: instances.to_vec(),
+            velocities: solver.velocities.clone(),
+            densities: solver.densities.clone(),
+            pressures: solver.pressures.clone(),
+            time: solver.time,
+            steps: solver.steps,
+        }
+    }
+
+    pub fn restore(&self, instances: &mut Vec<Instance>, solver: &mut Solver) {
+        instances.clone_from(&self.instances);
+        solver.velocities.clone_from(&self.velocities);
+        solver.densities.clone_from(&self.densities);
+        solver.pressures.clone_from(&self.pressures);
+        solver.time = self.time;
+        solver.steps = self.steps;
+    }
+}
+
+
+/// Fixed timestep, pause, single step, speed and rewind of the simulation
+///
+/// Real time is accumulated and spent in fixed steps of the solver timestep,
+/// so the result doesn't depend on the frame rate. Every step pushes the
+/// state before it into `history`, rewinding pops it back. The solver is
+/// deterministic, so stepping forward again reproduces the same frames.
+#[derive(Debug, Clone)]
+pub struct Playback {
+    pub paused: bool,
+    /// Simulated seconds per real second
+    pub speed: f32,
+    /// Solver steps a fixed step is split into, each advancing `timestep / substeps`
+    pub substeps: u32,
+    /// Most fixed steps run per update, time beyond that is dropped so a slow frame can't snowball
+    pub max_steps_per_update: u32,
+    /// Render between the last two steps instead of snapping to the latest
+    pub interpolate: bool,
+    /// Snapshots kept for rewinding, 0 disables the history
+    pub history_capacity: usize,
+    pub history: VecDeque<ParticleSnapshot>,
+    requested_steps: u32,
+    /// Real time not yet simulated, in simulated seconds
+    accumulator: f32,
+    last_update: Option<Instant>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            paused: false,
+            speed: 1.0,
+            substeps: 1,
+            max_steps_per_update: 4,
+            interpolate: true,
+            history_capacity: 120,
+            history: VecDeque::new(),
+            requested_steps: 0,
+            accumulator: 0.0,
+            last_update: None,
+        }
+    }
+}
+
+impl Playback {
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Pauses and runs exactly one step on the next update
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.requested_steps += 1;
+    }
+
+    /// Moves to the next (`faster`) or previous entry of [`PLAYBACK_SPEEDS`]
+    pub fn change_speed(&mut self, faster: bool) {
+        let current = PLAYBACK_SPEEDS.iter()
+            .position(|speed| *speed >= self.speed)
+            .unwrap_or(PLAYBACK_SPEEDS.len() - 1);
+
+        let next = match faster {
+            true => (current + 1).min(PLAYBACK_SPEEDS.len() - 1),
+            false => current.saturating_sub(1),
+        };
+        self.speed = PLAYBACK_SPEEDS[next];
+    }
+
+    /// Number of fixed steps of `timestep` seconds to run for the time passed until `now`
+    pub fn advance(&mut self, now: Instant, timestep: f32) -> u32 {
+        let elapsed = self.last_update.map_or(0.0, |last_update| (now - last_update).as_secs_f32());
+        self.last_update = Some(now);
+
+        self.accumulate(elapsed, timestep, true)
+    }
+
+    /// Number of fixed steps for exactly `elapsed` seconds, however long the frame really took
+    ///
+    /// Used while recording, where every frame is `1 / framerate` apart in the video. Steps
+    /// beyond `max_steps_per_update` aren't dropped, the video would skip ahead otherwise.
+    pub fn advance_by(&mut self, elapsed: f32, timestep: f32) -> u32 {
+        // Real time picks up from here once the recording stops
+        self.last_update = Some(Instant::now());
+
+        self.accumulate(elapsed, timestep, false)
+    }
+
+    fn accumulate(&mut self, elapsed: f32, timestep: f32, limit_steps: bool) -> u32 {
+        if self.paused || timestep <= 0.0 {
+            self.accumulator = 0.0;
+            return std::mem::take(&mut self.requested_steps);
+        }
+
+        self.accumulator += elapsed * self.speed;
+        let mut steps = (self.accumulator / timestep) as u32;
+        self.accumulator -= steps as f32 * timestep;
+
+        if limit_steps && steps > self.max_steps_per_update {
+            steps = self.max_steps_per_update;
+            self.accumulator %= timestep;
+        }
+
+        steps + std::mem::take(&mut self.requested_steps)
+    }
+
+    /// How far rendering is between the previous and the latest step, 1 shows the latest
+    pub fn interpolation_alpha(&self, timestep: f32) -> f32 {
+        match self.interpolate && !self.paused && timestep > 0.0 {
+            true => (self.accumulator / timestep).clamp(0.0, 1.0),
+            false => 1.0,
+        }
+    }
+
+    /// Stores the state before a step
+    pub fn record(&mut self, instances: &[Instance], solver: &Solver) {
+        if self.history_capacity == 0 {
+            return;
+        }
+
+        while self.history.len() >= self.history_capacity {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(ParticleSnapshot::capture(instances, solver));
+    }
+
+    /// Pauses and restores the state before the last step, returns false once the history is exhausted
+    pub fn rewind(&mut self, instances: &mut Vec<Instance>, solver: &mut Solver) -> bool {
+        self.paused = true;
+
+        match self.history.pop_back() {
+            Some(snapshot) => {
+                snapshot.restore(instances, solver);
+                true
+            }
+            None => false,
+        }
+    }
+}
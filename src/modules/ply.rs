@@ -0,0 +1,263 @@
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use crate::{ParticleFrame, ParticleFileError, attribute_token, with_header_capacity};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+impl PlyFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+            PlyFormat::BinaryBigEndian => "binary_big_endian",
+        }
+    }
+}
+
+/// Writes the [`ParticleFrame::columns`] as float properties of the vertex element, the time goes into a comment
+pub fn write_ply(writer: impl Write, frame: &ParticleFrame, format: PlyFormat) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let columns = frame.columns();
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", format.name())?;
+    writeln!(writer, "comment time {}", frame.time)?;
+    writeln!(writer, "element vertex {}", frame.len())?;
+    for (name, _) in &columns {
+        writeln!(writer, "property float {}", attribute_token(name))?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for index in 0..frame.len() {
+        match format {
+            PlyFormat::Ascii => {
+                let row = columns.iter()
+                    .map(|(_, values)| values[index].to_string())
+                    .collect::<Vec<_>>();
+                writeln!(writer, "{}", row.join(" "))?;
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for (_, values) in &columns {
+                    writer.write_all(&values[index].to_le_bytes())?;
+                }
+            }
+            PlyFormat::BinaryBigEndian => {
+                for (_, values) in &columns {
+                    writer.write_all(&values[index].to_be_bytes())?;
+                }
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => PlyType::I8,
+            "uchar" | "uint8" => PlyType::U8,
+            "short" | "int16" => PlyType::I16,
+            "ushort" | "uint16" => PlyType::U16,
+            "int" | "int32" => PlyType::I32,
+            "uint" | "uint32" => PlyType::U32,
+            "float" | "float32" => PlyType::F32,
+            "double" | "float64" => PlyType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PlyProperty {
+    Scalar { name: String, ty: PlyType },
+    /// Skipped when reading, only used to find where the next element starts
+    List { count_ty: PlyType, item_ty: PlyType },
+}
+
+#[derive(Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+enum PlyBody {
+    Ascii { tokens: std::vec::IntoIter<String> },
+    Binary { data: Vec<u8>, offset: usize, big_endian: bool },
+}
+
+impl PlyBody {
+    fn read(&mut self, ty: PlyType) -> Result<f64, ParticleFileError> {
+        match self {
+            PlyBody::Ascii { tokens } => {
+                let token = tokens.next().ok_or_else(|| ParticleFileError::parse(None, "ply body ended early"))?;
+                token.parse().map_err(|_| ParticleFileError::parse(None, format!("invalid ply value `{token}`")))
+            }
+            PlyBody::Binary { data, offset, big_endian } => {
+                let bytes = data.get(*offset..*offset + ty.size())
+                    .ok_or_else(|| ParticleFileError::parse(None, "ply body ended early"))?;
+                *offset += ty.size();
+
+                macro_rules! number {
+                    ($ty:ty) => {{
+                        let bytes = bytes.try_into().unwrap_or_default();
+                        let value = if *big_endian { <$ty>::from_be_bytes(bytes) } else { <$ty>::from_le_bytes(bytes) };
+                        value as f64
+                    }};
+                }
+
+                Ok(match ty {
+                    PlyType::I8 => number!(i8),
+                    PlyType::U8 => number!(u8),
+                    PlyType::I16 => number!(i16),
+                    PlyType::U16 => number!(u16),
+                    PlyType::I32 => number!(i32),
+                    PlyType::U32 => number!(u32),
+                    PlyType::F32 => number!(f32),
+                    PlyType::F64 => number!(f64),
+                })
+            }
+        }
+    }
+}
+
+/// Reads the scalar properties of the `vertex` element into a frame, see [`ParticleFrame::from_columns`]
+///
+/// Colors stored as `uchar` are scaled to 0..1, other elements like faces are skipped.
+pub fn read_ply(reader: impl Read) -> Result<ParticleFrame, ParticleFileError> {
+    let mut reader = BufReader::new(reader);
+    let mut format = None;
+    let mut time = 0.0;
+    let mut elements: Vec<PlyElement> = Vec::new();
+
+    let mut line_number = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(ParticleFileError::parse(line_number, "ply header has no end_header"));
+        }
+        line_number += 1;
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let invalid = || ParticleFileError::parse(line_number, format!("invalid ply header line `{}`", line.trim()));
+
+        match words.as_slice() {
+            ["ply"] if line_number == 1 => {}
+            _ if line_number == 1 => return Err(ParticleFileError::parse(1, "not a ply file")),
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(invalid()),
+                });
+            }
+            ["comment", "time", value] => time = value.parse().map_err(|_| invalid())?,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid())?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_ty, item_ty, _name] => {
+                let property = PlyProperty::List {
+                    count_ty: PlyType::parse(count_ty).ok_or_else(invalid)?,
+                    item_ty: PlyType::parse(item_ty).ok_or_else(invalid)?,
+                };
+                elements.last_mut().ok_or_else(invalid)?.properties.push(property);
+            }
+            ["property", ty, name] => {
+                let property = PlyProperty::Scalar {
+                    name: name.to_string(),
+                    ty: PlyType::parse(ty).ok_or_else(invalid)?,
+                };
+                elements.last_mut().ok_or_else(invalid)?.properties.push(property);
+            }
+            ["end_header"] => break,
+            _ => return Err(invalid()),
+        }
+    }
+
+    let format = format.ok_or_else(|| ParticleFileError::parse(None, "ply header has no format"))?;
+    let mut body = match format {
+        PlyFormat::Ascii => {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            let tokens = text.split_whitespace().map(str::to_owned).collect::<Vec<_>>();
+            PlyBody::Ascii { tokens: tokens.into_iter() }
+        }
+        PlyFormat::BinaryLittleEndian | PlyFormat::BinaryBigEndian => {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            PlyBody::Binary { data, offset: 0, big_endian: format == PlyFormat::BinaryBigEndian }
+        }
+    };
+
+    for element in &elements {
+        let is_vertex = element.name == "vertex";
+        let mut columns = element.properties.iter()
+            .filter_map(|property| match property {
+                PlyProperty::Scalar { name, .. } => Some((name.clone(), with_header_capacity(element.count))),
+                PlyProperty::List { .. } => None,
+            })
+            .collect::<Vec<(String, Vec<f32>)>>();
+
+        for _ in 0..element.count {
+            let mut column = 0;
+            for property in &element.properties {
+                match property {
+                    PlyProperty::Scalar { name, ty } => {
+                        let mut value = body.read(*ty)?;
+                        if *ty == PlyType::U8 && matches!(name.as_str(), "red" | "green" | "blue") {
+                            value /= 255.0;
+                        }
+
+                        columns[column].1.push(value as f32);
+                        column += 1;
+                    }
+                    PlyProperty::List { count_ty, item_ty } => {
+                        for _ in 0..body.read(*count_ty)? as usize {
+                            body.read(*item_ty)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        if is_vertex {
+            let mut frame = ParticleFrame::from_columns(columns)?;
+            frame.time = time;
+            return Ok(frame);
+        }
+    }
+
+    Err(ParticleFileError::parse(None, "ply file has no vertex element"))
+}
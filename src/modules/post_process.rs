@@ -0,0 +1,306 @@
+use wgpu::util::DeviceExt;
+use crate::{
+    RendererError,
+    RenderTargetRegistry, RenderTargetDescriptor, RenderTargetId,
+};
+
+
+/// Format of the scene target everything is rendered to before tonemapping
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// Clamps to the displayable range
+    None = 0,
+    Reinhard = 1,
+    Aces = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PostProcessSettings {
+    /// Linear multiplier applied before tonemapping
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    pub bloom: bool,
+    /// Brightness above which pixels start to bloom
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+        PostProcessSettings {
+            exposure: 1.0,
+            tonemapper: Tonemapper::Aces,
+            bloom: false,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.5,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostProcessUniform {
+    pub exposure: f32,
+    pub tonemapper: u32,
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+}
+
+impl From<&PostProcessSettings> for PostProcessUniform {
+    fn from(settings: &PostProcessSettings) -> Self {
+        PostProcessUniform {
+            exposure: settings.exposure,
+            tonemapper: settings.tonemapper as u32,
+            bloom_threshold: settings.bloom_threshold,
+            bloom_intensity: if settings.bloom { settings.bloom_intensity } else { 0.0 },
+        }
+    }
+}
+
+
+/// Resolves the hdr scene target to the surface, optionally adding bloom
+///
+/// Bloom is extracted at half resolution into `bloom_targets[0]`, blurred
+/// horizontally into `[1]` and vertically back into `[0]`.
+pub struct PostProcess {
+    pub settings: PostProcessSettings,
+    pub hdr_target: RenderTargetId,
+    bloom_targets: [RenderTargetId; 2],
+
+    uniform_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+
+    bright_pipeline: wgpu::RenderPipeline,
+    blur_horizontal_pipeline: wgpu::RenderPipeline,
+    blur_vertical_pipeline: wgpu::RenderPipeline,
+    tonemap_pipeline: wgpu::RenderPipeline,
+
+    bind_groups: PostProcessBindGroups,
+}
+
+/// Bind groups reference the textures, so they have to be rebuilt with them
+struct PostProcessBindGroups {
+    bright: wgpu::BindGroup,
+    blur_horizontal: wgpu::BindGroup,
+    blur_vertical: wgpu::BindGroup,
+    tonemap: wgpu::BindGroup,
+}
+
+impl PostProcess {
+    fn init_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+        targets: &RenderTargetRegistry,
+        hdr_target: RenderTargetId,
+        bloom_targets: [RenderTargetId; 2],
+    ) -> PostProcessBindGroups {
+        let create_bind_group = |source: RenderTargetId, bloom: RenderTargetId, label| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(targets.view(source)),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(targets.view(bloom)),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: uniform_buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some(label),
+            })
+        };
+
+        let [bloom_0, bloom_1] = bloom_targets;
+
+        // A pass can't sample the texture it renders to, the unused bloom slot points elsewhere
+        PostProcessBindGroups {
+            bright: create_bind_group(hdr_target, bloom_1, "bloom_bright_bind_group"),
+            blur_horizontal: create_bind_group(bloom_0, bloom_0, "bloom_blur_horizontal_bind_group"),
+            blur_vertical: create_bind_group(bloom_1, bloom_1, "bloom_blur_vertical_bind_group"),
+            tonemap: create_bind_group(hdr_target, bloom_0, "tonemap_bind_group"),
+        }
+    }
+
+    fn init_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, entry_point: &str, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    /// Registers the hdr and bloom targets, `output_format` is the format of the pass `draw_tonemap` is used in
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat, targets: &mut RenderTargetRegistry, settings: PostProcessSettings) -> Result<Self, RendererError> {
+        let mut preprocessor = crate::shader_preprocessor();
+        preprocessor.add_source("shaders/fullscreen.wgsl", include_str!("../shaders/fullscreen.wgsl"));
+        let processed = preprocessor.process_str("shaders/post_process.wgsl", include_str!("../shaders/post_process.wgsl"))?;
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Process Shader"),
+            source: wgpu::ShaderSource::Wgsl(processed.source.into()),
+        });
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Process Buffer"),
+            contents: bytemuck::cast_slice(&[PostProcessUniform::from(&settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("Post process bind group layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Process Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let bright_pipeline = Self::init_pipeline(device, &pipeline_layout, &shader, "fs_bright", HDR_FORMAT);
+        let blur_horizontal_pipeline = Self::init_pipeline(device, &pipeline_layout, &shader, "fs_blur_horizontal", HDR_FORMAT);
+        let blur_vertical_pipeline = Self::init_pipeline(device, &pipeline_layout, &shader, "fs_blur_vertical", HDR_FORMAT);
+        let tonemap_pipeline = Self::init_pipeline(device, &pipeline_layout, &shader, "fs_tonemap", output_format);
+
+        let hdr_target = targets.register(device, RenderTargetDescriptor::new("hdr_texture", HDR_FORMAT));
+        let bloom_targets = [
+            targets.register(device, RenderTargetDescriptor::new("bloom_texture_0", HDR_FORMAT).with_scale(0.5)),
+            targets.register(device, RenderTargetDescriptor::new("bloom_texture_1", HDR_FORMAT).with_scale(0.5)),
+        ];
+        let bind_groups = Self::init_bind_groups(device, &bind_group_layout, &sampler, &uniform_buffer, targets, hdr_target, bloom_targets);
+
+        Ok(PostProcess {
+            settings,
+            hdr_target,
+            bloom_targets,
+            uniform_buffer,
+            sampler,
+            bind_group_layout,
+            bright_pipeline,
+            blur_horizontal_pipeline,
+            blur_vertical_pipeline,
+            tonemap_pipeline,
+            bind_groups,
+        })
+    }
+
+    /// Rebuilds the bind groups after `targets` were resized
+    pub fn resize(&mut self, device: &wgpu::Device, targets: &RenderTargetRegistry) {
+        self.bind_groups = Self::init_bind_groups(device, &self.bind_group_layout, &self.sampler, &self.uniform_buffer, targets, self.hdr_target, self.bloom_targets);
+    }
+
+    /// Uploads `settings`, call after changing them
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[PostProcessUniform::from(&self.settings)]));
+    }
+
+    fn fullscreen_pass(encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup, label: &str) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Records the bloom passes, does nothing when bloom is disabled
+    pub fn encode_bloom(&self, encoder: &mut wgpu::CommandEncoder, targets: &RenderTargetRegistry) {
+        if !self.settings.bloom {
+            return;
+        }
+
+        let [bloom_0, bloom_1] = self.bloom_targets.map(|id| targets.view(id));
+        Self::fullscreen_pass(encoder, bloom_0, &self.bright_pipeline, &self.bind_groups.bright, "Bloom Bright Pass");
+        Self::fullscreen_pass(encoder, bloom_1, &self.blur_horizontal_pipeline, &self.bind_groups.blur_horizontal, "Bloom Horizontal Blur Pass");
+        Self::fullscreen_pass(encoder, bloom_0, &self.blur_vertical_pipeline, &self.bind_groups.blur_vertical, "Bloom Vertical Blur Pass");
+    }
+
+    /// Draws the tonemapped scene into a pass targeting the surface
+    pub fn draw_tonemap<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.tonemap_pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups.tonemap, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
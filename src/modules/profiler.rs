@@ -0,0 +1,232 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, atomic::{AtomicU8, Ordering}},
+    time::{Duration, Instant},
+};
+
+
+/// Frames kept for the frame time graph
+pub const PROFILER_HISTORY: usize = 240;
+
+#[derive(Debug, Clone)]
+pub struct StageTiming {
+    pub name: String,
+    /// Milliseconds spent in the last frame
+    pub last: f32,
+    /// Exponential moving average in milliseconds
+    pub average: f32,
+}
+
+/// Frame times and per stage cpu timings, plus gpu pass timings when timestamp queries are supported
+pub struct Profiler {
+    /// Milliseconds between consecutive frames, oldest first
+    pub frame_times: VecDeque<f32>,
+    pub cpu_stages: Vec<StageTiming>,
+    pub gpu_passes: Vec<StageTiming>,
+    /// `None` when the device doesn't support timestamp queries
+    pub gpu_timer: Option<GpuTimer>,
+    last_frame: Option<Instant>,
+}
+
+impl Profiler {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let gpu_timer = device.features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| GpuTimer::new(device, queue, GpuTimer::DEFAULT_CAPACITY));
+
+        Profiler {
+            frame_times: VecDeque::with_capacity(PROFILER_HISTORY),
+            cpu_stages: Vec::new(),
+            gpu_passes: Vec::new(),
+            gpu_timer,
+            last_frame: None,
+        }
+    }
+
+    /// Records the time since the previous call as a frame
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == PROFILER_HISTORY {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back((now - last_frame).as_secs_f32() * 1000.0);
+        }
+
+        self.last_frame = Some(now);
+    }
+
+    fn record(stages: &mut Vec<StageTiming>, name: &str, milliseconds: f32) {
+        match stages.iter_mut().find(|stage| stage.name == name) {
+            Some(stage) => {
+                stage.last = milliseconds;
+                stage.average += (milliseconds - stage.average) * 0.05;
+            }
+            None => stages.push(StageTiming {
+                name: name.to_owned(),
+                last: milliseconds,
+                average: milliseconds,
+            }),
+        }
+    }
+
+    pub fn record_cpu(&mut self, stage: &str, duration: Duration) {
+        Self::record(&mut self.cpu_stages, stage, duration.as_secs_f32() * 1000.0);
+    }
+
+    /// Runs `f` and records how long it took as `stage`
+    pub fn time<T>(&mut self, stage: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record_cpu(stage, start.elapsed());
+
+        result
+    }
+
+    /// Picks up gpu timings of earlier frames once they're read back
+    pub fn poll_gpu(&mut self, device: &wgpu::Device) {
+        let Some(timings) = self.gpu_timer.as_mut().and_then(|timer| timer.read(device)) else {
+            return;
+        };
+
+        for (pass, milliseconds) in timings {
+            Self::record(&mut self.gpu_passes, &pass, milliseconds);
+        }
+    }
+
+    pub fn average_frame_time(&self) -> f32 {
+        match self.frame_times.len() {
+            0 => 0.0,
+            len => self.frame_times.iter().sum::<f32>() / len as f32,
+        }
+    }
+}
+
+
+/// Writes a timestamp after every pass and reads them back a few frames later
+///
+/// While a readback is in flight no new timestamps are written, so some frames go unmeasured.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    readback_buffer: wgpu::Buffer,
+    capacity: u32,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Pass labels of the frame being measured, `labels[i]` ends at timestamp `i + 1`
+    labels: Vec<String>,
+    in_flight: bool,
+    /// Set by the map callback, see the `MAP_*` constants
+    map_state: Arc<AtomicU8>,
+}
+
+impl GpuTimer {
+    pub const DEFAULT_CAPACITY: u32 = 32;
+
+    const MAP_PENDING: u8 = 0;
+    const MAP_DONE: u8 = 1;
+    const MAP_FAILED: u8 = 2;
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, capacity: u32) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: capacity,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Buffer"),
+            size: capacity as u64 * wgpu::QUERY_SIZE as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        GpuTimer {
+            query_set,
+            readback_buffer,
+            capacity,
+            period: queue.get_timestamp_period(),
+            labels: Vec::new(),
+            in_flight: false,
+            map_state: Arc::new(AtomicU8::new(Self::MAP_PENDING)),
+        }
+    }
+
+    /// Whether timestamps are written this frame
+    pub fn is_measuring(&self) -> bool {
+        !self.in_flight
+    }
+
+    /// Marks the start of the first pass
+    pub fn begin(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.is_measuring() {
+            self.labels.clear();
+            encoder.write_timestamp(&self.query_set, 0);
+        }
+    }
+
+    /// Marks the end of `pass`, which started where the previous one ended
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder, pass: &str) {
+        let index = self.labels.len() as u32 + 1;
+        if self.is_measuring() && index < self.capacity {
+            encoder.write_timestamp(&self.query_set, index);
+            self.labels.push(pass.to_owned());
+        }
+    }
+
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.is_measuring() && !self.labels.is_empty() {
+            encoder.resolve_query_set(&self.query_set, 0..self.labels.len() as u32 + 1, &self.readback_buffer, 0);
+        }
+    }
+
+    /// Starts reading back the resolved timestamps, call after submitting
+    pub fn map(&mut self) {
+        if self.in_flight || self.labels.is_empty() {
+            return;
+        }
+
+        self.in_flight = true;
+        let map_state = self.map_state.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let state = if result.is_ok() { Self::MAP_DONE } else { Self::MAP_FAILED };
+                map_state.store(state, Ordering::Release);
+            });
+    }
+
+    /// Milliseconds per pass, once the readback finished
+    pub fn read(&mut self, device: &wgpu::Device) -> Option<Vec<(String, f32)>> {
+        if !self.in_flight {
+            return None;
+        }
+
+        device.poll(wgpu::Maintain::Poll);
+        match self.map_state.swap(Self::MAP_PENDING, Ordering::Acquire) {
+            Self::MAP_DONE => {}
+            Self::MAP_FAILED => {
+                self.in_flight = false;
+                return None;
+            }
+            _ => return None,
+        }
+
+        let timings = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+
+            self.labels.iter()
+                .enumerate()
+                .map(|(index, label)| {
+                    let ticks = timestamps[index + 1].saturating_sub(timestamps[index]);
+                    (label.clone(), ticks as f32 * self.period / 1_000_000.0)
+                })
+                .collect()
+        };
+
+        self.readback_buffer.unmap();
+        self.in_flight = false;
+
+        Some(timings)
+    }
+}
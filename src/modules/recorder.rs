@@ -0,0 +1,351 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+    thread::JoinHandle,
+};
+use crate::CapturedFrame;
+
+
+/// Where the ui records videos and png sequences to
+pub const DEFAULT_VIDEO_PATH: &str = "recording.mp4";
+pub const DEFAULT_PNG_DIRECTORY: &str = "recording";
+pub const DEFAULT_FRAMERATE: u32 = 60;
+
+#[derive(Debug)]
+pub enum RecorderError {
+    Io(io::Error),
+    Png(png::EncodingError),
+    /// The rendered frame couldn't be read back from the gpu
+    Capture(wgpu::BufferAsyncError),
+    /// The encoder program couldn't be started because it doesn't exist
+    FfmpegNotFound(PathBuf),
+    /// The encoder exited early or with an error, `stderr` is what it printed
+    Ffmpeg { status: Option<ExitStatus>, stderr: String },
+    /// Videos have a fixed size, e.g. the window was resized while recording
+    FrameSize { expected: (u32, u32), found: (u32, u32) },
+}
+
+impl fmt::Display for RecorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Png(error) => write!(f, "{error}"),
+            Self::Capture(error) => write!(f, "failed to read the frame back: {error}"),
+            Self::FfmpegNotFound(program) => write!(
+                f,
+                "`{}` wasn't found, install ffmpeg and make sure it's on the PATH or set `VideoSettings::program`",
+                program.display(),
+            ),
+            Self::Ffmpeg { status, stderr } => {
+                match status {
+                    Some(status) => write!(f, "ffmpeg failed ({status})")?,
+                    None => write!(f, "ffmpeg stopped reading frames")?,
+                }
+                match stderr.trim() {
+                    "" => Ok(()),
+                    stderr => write!(f, ": {stderr}"),
+                }
+            }
+            Self::FrameSize { expected, found } => write!(
+                f,
+                "frame is {}x{} but the video is {}x{}",
+                found.0, found.1, expected.0, expected.1,
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecorderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Png(error) => Some(error),
+            Self::Capture(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RecorderError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<png::EncodingError> for RecorderError {
+    fn from(error: png::EncodingError) -> Self {
+        Self::Png(error)
+    }
+}
+
+impl From<wgpu::BufferAsyncError> for RecorderError {
+    fn from(error: wgpu::BufferAsyncError) -> Self {
+        Self::Capture(error)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    /// `libx264` in an mp4, plays nearly everywhere
+    H264,
+    /// `libx265`, smaller files than h264 at the same quality
+    H265,
+    /// `libvpx-vp9` in a webm, for browsers
+    Vp9,
+}
+
+impl VideoCodec {
+    /// Picks the codec from the container, vp9 for `.webm` and h264 otherwise
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("webm") => VideoCodec::Vp9,
+            _ => VideoCodec::H264,
+        }
+    }
+
+    fn encoder(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+            VideoCodec::Vp9 => "libvpx-vp9",
+        }
+    }
+
+    /// Constant rate factor ffmpeg would pick on its own
+    pub fn default_crf(&self) -> u32 {
+        match self {
+            VideoCodec::H264 => 23,
+            VideoCodec::H265 => 28,
+            VideoCodec::Vp9 => 31,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoSettings {
+    pub path: PathBuf,
+    pub framerate: u32,
+    pub codec: VideoCodec,
+    /// Constant rate factor, lower is better quality and bigger files, `None` uses the codec default
+    pub crf: Option<u32>,
+    /// Encoder to run, looked up on the PATH unless it's a path
+    pub program: PathBuf,
+}
+
+impl VideoSettings {
+    /// [`DEFAULT_FRAMERATE`] with the codec picked from the extension, e.g. `capture.mp4` or `capture.webm`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+
+        VideoSettings {
+            codec: VideoCodec::for_path(&path),
+            path,
+            framerate: DEFAULT_FRAMERATE,
+            crf: None,
+            program: PathBuf::from("ffmpeg"),
+        }
+    }
+
+    pub fn framerate(mut self, framerate: u32) -> Self {
+        self.framerate = framerate;
+        self
+    }
+
+    pub fn codec(mut self, codec: VideoCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn crf(mut self, crf: u32) -> Self {
+        self.crf = Some(crf);
+        self
+    }
+
+    pub fn program(mut self, program: impl Into<PathBuf>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Arguments ffmpeg gets for raw rgba frames of `size` on stdin
+    pub fn arguments(&self, size: (u32, u32)) -> Vec<String> {
+        let crf = self.crf.unwrap_or(self.codec.default_crf());
+        let mut arguments = [
+            "-hide_banner", "-loglevel", "error", "-y",
+            "-f", "rawvideo", "-pix_fmt", "rgba",
+            "-s", &format!("{}x{}", size.0, size.1),
+            "-r", &self.framerate.to_string(),
+            "-i", "-",
+            "-c:v", self.codec.encoder(),
+            "-crf", &crf.to_string(),
+            // yuv420p needs even dimensions, windows can have odd ones
+            "-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            "-pix_fmt", "yuv420p",
+        ]
+        .map(str::to_owned)
+        .to_vec();
+
+        match self.codec {
+            // Without a bitrate of 0 the crf is only an upper bound for vp9
+            VideoCodec::Vp9 => arguments.extend(["-b:v", "0"].map(str::to_owned)),
+            // Quicktime only plays hevc tagged as hvc1
+            VideoCodec::H265 => arguments.extend(["-tag:v", "hvc1"].map(str::to_owned)),
+            VideoCodec::H264 => {}
+        }
+        arguments.push(self.path.to_string_lossy().into_owned());
+
+        arguments
+    }
+}
+
+
+/// A spawned ffmpeg that encodes the raw frames written to its stdin
+pub struct VideoEncoder {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    /// Drains stderr so a chatty encoder can't block on a full pipe
+    stderr: Option<JoinHandle<String>>,
+    size: (u32, u32),
+}
+
+impl VideoEncoder {
+    pub fn spawn(settings: &VideoSettings, size: (u32, u32)) -> Result<Self, RecorderError> {
+        let mut child = Command::new(&settings.program)
+            .args(settings.arguments(size))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::NotFound => RecorderError::FfmpegNotFound(settings.program.clone()),
+                _ => RecorderError::Io(error),
+            })?;
+
+        let stdin = child.stdin.take();
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut output = String::new();
+                // Whatever was read before an error is still worth reporting
+                let _ = stderr.read_to_string(&mut output);
+                output
+            })
+        });
+
+        Ok(VideoEncoder { child, stdin, stderr, size })
+    }
+
+    pub fn write_frame(&mut self, frame: &CapturedFrame) -> Result<(), RecorderError> {
+        if (frame.width, frame.height) != self.size {
+            return Err(RecorderError::FrameSize { expected: self.size, found: (frame.width, frame.height) });
+        }
+
+        let Some(stdin) = self.stdin.as_mut() else {
+            return Err(self.failure());
+        };
+        match stdin.write_all(&frame.data) {
+            Ok(()) => Ok(()),
+            // A closed pipe means ffmpeg exited, its output says why
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Err(self.failure()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Closes stdin so ffmpeg finishes the file, and waits for it
+    pub fn finish(mut self) -> Result<(), RecorderError> {
+        self.stdin = None;
+        let status = self.child.wait()?;
+
+        match status.success() {
+            true => Ok(()),
+            false => Err(RecorderError::Ffmpeg { status: Some(status), stderr: self.stderr() }),
+        }
+    }
+
+    fn failure(&mut self) -> RecorderError {
+        self.stdin = None;
+        let status = self.child.wait().ok();
+
+        RecorderError::Ffmpeg { status, stderr: self.stderr() }
+    }
+
+    fn stderr(&mut self) -> String {
+        self.stderr.take()
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for VideoEncoder {
+    fn drop(&mut self) {
+        // Dropped without finish, e.g. after an error, don't leave the process behind
+        if self.stdin.take().is_some() {
+            let _ = self.child.wait();
+        }
+    }
+}
+
+
+pub enum RecordingOutput {
+    /// `frame_00000.png`, `frame_00001.png`, ... in the directory
+    PngSequence(PathBuf),
+    Video(VideoEncoder),
+}
+
+/// Writes captured frames to a png sequence or a video, one per rendered frame
+///
+/// The simulation advances by exactly one frame interval per rendered frame while
+/// recording, so the result plays at the right speed however slow rendering is.
+pub struct Recorder {
+    pub output: RecordingOutput,
+    /// Frames per second of the result, for png sequences only the simulated time between them
+    pub framerate: u32,
+    frames: u32,
+}
+
+impl Recorder {
+    pub fn png_sequence(directory: impl Into<PathBuf>) -> Result<Self, RecorderError> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(Recorder { output: RecordingOutput::PngSequence(directory), framerate: DEFAULT_FRAMERATE, frames: 0 })
+    }
+
+    /// Starts ffmpeg for frames of `size`, fails right away when it isn't installed
+    pub fn video(settings: &VideoSettings, size: (u32, u32)) -> Result<Self, RecorderError> {
+        Ok(Recorder {
+            output: RecordingOutput::Video(VideoEncoder::spawn(settings, size)?),
+            framerate: settings.framerate,
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Seconds between two frames
+    pub fn frame_interval(&self) -> f32 {
+        1.0 / self.framerate.max(1) as f32
+    }
+
+    pub fn write_frame(&mut self, frame: &CapturedFrame) -> Result<(), RecorderError> {
+        match &mut self.output {
+            RecordingOutput::PngSequence(directory) => {
+                frame.save_png(directory.join(format!("frame_{:05}.png", self.frames)))?;
+            }
+            RecordingOutput::Video(encoder) => encoder.write_frame(frame)?,
+        }
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), RecorderError> {
+        match self.output {
+            RecordingOutput::PngSequence(_) => Ok(()),
+            RecordingOutput::Video(encoder) => encoder.finish(),
+        }
+    }
+}
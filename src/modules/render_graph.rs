@@ -0,0 +1,323 @@
+use std::{collections::{HashMap, HashSet}, fmt};
+use crate::{
+    State, RenderTexture, GpuTimer, FrameCapture,
+    RenderTargetRegistry, RenderTargetDescriptor, RenderTargetId,
+};
+
+
+/// Name of the frame's surface texture, always available to nodes
+pub const SURFACE: &str = "surface";
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+    /// The listed nodes depend on each other
+    Cycle(Vec<String>),
+    /// A node reads or writes a resource nothing provides
+    UnknownResource { node: String, resource: String },
+    DuplicateNode(String),
+}
+
+impl fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(nodes) => write!(f, "render graph has a cycle between {}", nodes.join(", ")),
+            Self::UnknownResource { node, resource } => write!(f, "node `{node}` uses unknown resource `{resource}`"),
+            Self::DuplicateNode(node) => write!(f, "render graph already has a node named `{node}`"),
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {}
+
+
+/// Textures available to nodes while recording, looked up by name
+pub struct GraphResources<'a> {
+    targets: &'a RenderTargetRegistry,
+    names: &'a HashMap<String, RenderTargetId>,
+    surface: Option<&'a wgpu::TextureView>,
+}
+
+impl<'a> GraphResources<'a> {
+    pub fn target(&self, name: &str) -> Option<&'a RenderTexture> {
+        self.names.get(name).map(|id| self.targets.get(*id))
+    }
+
+    /// Panics if `name` is unknown, the graph checks declared resources when compiling
+    pub fn view(&self, name: &str) -> &'a wgpu::TextureView {
+        match (name, self.surface) {
+            (SURFACE, Some(surface)) => surface,
+            _ => &self
+                .target(name)
+                .unwrap_or_else(|| panic!("unknown render graph resource `{name}`"))
+                .view,
+        }
+    }
+
+    pub fn targets(&self) -> &'a RenderTargetRegistry {
+        self.targets
+    }
+}
+
+
+pub trait RenderNode {
+    /// Unique name of the node
+    fn label(&self) -> &str;
+
+    /// Resources sampled by this node
+    fn reads(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Resources rendered to by this node
+    fn writes(&self) -> Vec<String>;
+
+    /// Targets the graph allocates for this node, named by their label
+    fn transients(&self) -> Vec<RenderTargetDescriptor> {
+        Vec::new()
+    }
+
+    /// Called before the first frame and whenever the targets were recreated,
+    /// bind groups referencing graph resources should be rebuilt here
+    #[allow(unused_variables)]
+    fn prepare(&mut self, state: &State, resources: &GraphResources) {}
+
+    fn record(&mut self, state: &State, resources: &GraphResources, encoder: &mut wgpu::CommandEncoder);
+}
+
+
+/// Draws on top of the finished frame, e.g. the ui, gizmos or a hud
+///
+/// All overlays of a frame share one pass on the surface, recorded after every node.
+pub trait Overlay {
+    fn record<'a>(&'a mut self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>);
+}
+
+
+/// Orders render nodes by the resources they read and write and records them into one encoder
+///
+/// A node that reads a resource runs after every node writing it, nodes writing
+/// the same resource run in the order they were added.
+#[derive(Default)]
+pub struct RenderGraph {
+    nodes: Vec<Box<dyn RenderNode>>,
+    names: HashMap<String, RenderTargetId>,
+    /// Names in `names` the graph allocated for node transients, as opposed to imported ones
+    transients: HashSet<String>,
+    order: Vec<usize>,
+    /// Registry generation the nodes were last prepared for
+    prepared: Option<u64>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes a target owned elsewhere available to nodes as `name`
+    pub fn import(&mut self, name: &str, id: RenderTargetId) {
+        self.names.insert(name.to_owned(), id);
+    }
+
+    /// Adds `node` and recompiles, allocating its transient targets in `targets`
+    pub fn add_node(&mut self, node: impl RenderNode + 'static, targets: &mut RenderTargetRegistry, device: &wgpu::Device) -> Result<(), RenderGraphError> {
+        if self.nodes.iter().any(|existing| existing.label() == node.label()) {
+            return Err(RenderGraphError::DuplicateNode(node.label().to_owned()));
+        }
+
+        self.nodes.push(Box::new(node));
+        let compiled = self.compile(targets, device);
+        if compiled.is_err() {
+            self.nodes.pop();
+            self.free_unused_transients(targets);
+        }
+
+        compiled
+    }
+
+    /// Removes the node and frees the transient targets no remaining node declares
+    pub fn remove_node(&mut self, label: &str, targets: &mut RenderTargetRegistry) -> Option<Box<dyn RenderNode>> {
+        let index = self.nodes.iter().position(|node| node.label() == label)?;
+        let node = self.nodes.remove(index);
+        self.free_unused_transients(targets);
+
+        // Removing a node can't introduce cycles or unknown resources
+        self.order = Self::sort(&self.nodes).unwrap_or_default();
+        Some(node)
+    }
+
+    pub fn node_labels(&self) -> impl Iterator<Item = &str> {
+        self.order.iter().map(|index| self.nodes[*index].label())
+    }
+
+    /// Target a resource name refers to, imported or transient
+    pub fn resource(&self, name: &str) -> Option<RenderTargetId> {
+        self.names.get(name).copied()
+    }
+
+    fn free_unused_transients(&mut self, targets: &mut RenderTargetRegistry) {
+        let declared = self.nodes.iter()
+            .flat_map(|node| node.transients())
+            .map(|descriptor| descriptor.label)
+            .collect::<HashSet<_>>();
+
+        self.transients.retain(|name| {
+            if declared.contains(name) {
+                return true;
+            }
+            if let Some(id) = self.names.remove(name) {
+                targets.unregister(id);
+            }
+            false
+        });
+    }
+
+    fn compile(&mut self, targets: &mut RenderTargetRegistry, device: &wgpu::Device) -> Result<(), RenderGraphError> {
+        for node in self.nodes.iter() {
+            for descriptor in node.transients() {
+                if !self.names.contains_key(&descriptor.label) {
+                    let name = descriptor.label.clone();
+                    self.transients.insert(name.clone());
+                    self.names.insert(name, targets.register(device, descriptor));
+                }
+            }
+        }
+
+        for node in self.nodes.iter() {
+            for resource in node.reads().into_iter().chain(node.writes()) {
+                if resource != SURFACE && !self.names.contains_key(&resource) {
+                    return Err(RenderGraphError::UnknownResource { node: node.label().to_owned(), resource });
+                }
+            }
+        }
+
+        self.order = Self::sort(&self.nodes)?;
+        self.prepared = None;
+
+        Ok(())
+    }
+
+    /// Stable topological sort, ties are broken by insertion order
+    fn sort(nodes: &[Box<dyn RenderNode>]) -> Result<Vec<usize>, RenderGraphError> {
+        let reads = nodes.iter().map(|node| node.reads()).collect::<Vec<_>>();
+        let writes = nodes.iter().map(|node| node.writes()).collect::<Vec<_>>();
+
+        let mut dependencies = vec![Vec::new(); nodes.len()];
+        for (node, node_dependencies) in dependencies.iter_mut().enumerate() {
+            for (other, other_writes) in writes.iter().enumerate() {
+                if other == node {
+                    continue;
+                }
+
+                let reads_output = reads[node].iter()
+                    .any(|resource| other_writes.contains(resource) && !writes[node].contains(resource));
+                let writes_after = other < node && writes[node].iter().any(|resource| other_writes.contains(resource));
+
+                if reads_output || writes_after {
+                    node_dependencies.push(other);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(nodes.len());
+        let mut done = vec![false; nodes.len()];
+
+        while order.len() < nodes.len() {
+            let next = (0..nodes.len())
+                .find(|node| !done[*node] && dependencies[*node].iter().all(|dependency| done[*dependency]));
+
+            match next {
+                Some(node) => {
+                    done[node] = true;
+                    order.push(node);
+                }
+                None => {
+                    let remaining = (0..nodes.len())
+                        .filter(|node| !done[*node])
+                        .map(|node| nodes[node].label().to_owned())
+                        .collect();
+                    return Err(RenderGraphError::Cycle(remaining));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+
+    /// Records every node, then `overlays` in the given order, into a single command buffer
+    ///
+    /// With a `gpu_timer` every node and the overlays are timed separately. A `capture` gets
+    /// its texture copied into the capture's buffer after the nodes, so it leaves out the overlays.
+    pub fn execute(
+        &mut self,
+        state: &State,
+        surface: &wgpu::TextureView,
+        overlays: &mut [&mut dyn Overlay],
+        mut gpu_timer: Option<&mut GpuTimer>,
+        capture: Option<(&wgpu::Texture, &FrameCapture)>,
+    ) -> wgpu::CommandBuffer {
+        let resources = GraphResources {
+            targets: &state.render_targets,
+            names: &self.names,
+            surface: Some(surface),
+        };
+
+        let generation = state.render_targets.generation();
+        if self.prepared != Some(generation) {
+            for node in self.nodes.iter_mut() {
+                node.prepare(state, &resources);
+            }
+            self.prepared = Some(generation);
+        }
+
+        let mut encoder = state
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Render Encoder"),
+            });
+
+        if let Some(timer) = gpu_timer.as_deref_mut() {
+            timer.begin(&mut encoder);
+        }
+
+        for index in self.order.iter() {
+            let node = &mut self.nodes[*index];
+            node.record(state, &resources, &mut encoder);
+
+            if let Some(timer) = gpu_timer.as_deref_mut() {
+                timer.end_pass(&mut encoder, node.label());
+            }
+        }
+
+        if let Some((texture, capture)) = capture {
+            capture.copy_from(&mut encoder, texture);
+        }
+
+        if !overlays.is_empty() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: surface,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            for overlay in overlays.iter_mut() {
+                overlay.record(state, &mut render_pass);
+            }
+        }
+
+        if let Some(timer) = gpu_timer {
+            if !overlays.is_empty() {
+                timer.end_pass(&mut encoder, "overlays");
+            }
+            timer.resolve(&mut encoder);
+        }
+
+        encoder.finish()
+    }
+}
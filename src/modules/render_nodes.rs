@@ -0,0 +1,105 @@
+use crate::{State, RenderNode, Overlay, GraphResources, SURFACE};
+
+
+/// Hdr scene target, tonemapped to the surface by [`PostProcessNode`]
+pub const HDR: &str = "hdr";
+pub const DEPTH: &str = "depth";
+/// Multisampled color target, only present when msaa is enabled
+pub const MSAA: &str = "msaa";
+
+
+/// Draws the instanced particle sprites into the hdr and depth targets
+pub struct ParticleNode;
+
+impl RenderNode for ParticleNode {
+    fn label(&self) -> &str {
+        "particles"
+    }
+
+    fn writes(&self) -> Vec<String> {
+        vec![HDR.to_owned(), DEPTH.to_owned()]
+    }
+
+    fn record(&mut self, state: &State, resources: &GraphResources, encoder: &mut wgpu::CommandEncoder) {
+        let hdr_view = resources.view(HDR);
+        let msaa_view = resources.target(MSAA).map(|msaa| &msaa.view);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: msaa_view.unwrap_or(hdr_view),
+                resolve_target: msaa_view.map(|_| hdr_view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(state.clear_color),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: resources.view(DEPTH),
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+
+        render_pass.set_pipeline(&state.render_pipeline);
+        render_pass.set_bind_group(0, &state.camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &state.render_settings_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, state.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, state.instance_buffer().slice(..));
+        render_pass.set_index_buffer(state.index_buffer.slice(..), state.index_format);
+        render_pass.draw_indexed(0..state.num_indices, 0, 0..state.num_instances);
+    }
+}
+
+
+/// Bloom and tonemapping of the hdr target onto the surface
+pub struct PostProcessNode;
+
+impl RenderNode for PostProcessNode {
+    fn label(&self) -> &str {
+        "post_process"
+    }
+
+    fn reads(&self) -> Vec<String> {
+        vec![HDR.to_owned()]
+    }
+
+    fn writes(&self) -> Vec<String> {
+        vec![SURFACE.to_owned()]
+    }
+
+    fn record(&mut self, state: &State, resources: &GraphResources, encoder: &mut wgpu::CommandEncoder) {
+        state.post_process.encode_bloom(encoder, resources.targets());
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: resources.view(SURFACE),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        state.post_process.draw_tonemap(&mut render_pass);
+    }
+}
+
+
+/// Draws imgui on top of the frame, built for a single frame
+pub struct ImguiOverlay<'a> {
+    pub renderer: &'a mut imgui_wgpu::Renderer,
+    pub draw_data: &'a imgui::DrawData,
+}
+
+impl Overlay for ImguiOverlay<'_> {
+    fn record<'a>(&'a mut self, state: &'a State, render_pass: &mut wgpu::RenderPass<'a>) {
+        self.renderer.render(self.draw_data, &state.queue, &state.device, render_pass).expect("Falied to render ui");
+    }
+}
@@ -0,0 +1,127 @@
+use glam::{vec3, Vec3};
+use crate::{Solver, SolverParams};
+
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Discs darkening towards their edge
+    Soft = 0,
+    Flat = 1,
+    /// The whole sprite quad, useful to see overdraw
+    Square = 2,
+}
+
+/// Per-particle value the instances are colored by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMap {
+    /// Keep the instance colors
+    Instance,
+    Speed,
+    Density,
+    Pressure,
+}
+
+impl ColorMap {
+    pub const ALL: [ColorMap; 4] = [ColorMap::Instance, ColorMap::Speed, ColorMap::Density, ColorMap::Pressure];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Instance => "instance",
+            Self::Speed => "speed",
+            Self::Density => "density",
+            Self::Pressure => "pressure",
+        }
+    }
+
+    /// `None` for [`ColorMap::Instance`] or when the solver has no value for `index`
+    pub fn value(&self, solver: &Solver, index: usize) -> Option<f32> {
+        match self {
+            Self::Instance => None,
+            Self::Speed => solver.velocities.get(index).map(|velocity| velocity.length()),
+            Self::Density => solver.densities.get(index).copied(),
+            Self::Pressure => solver.pressures.get(index).copied(),
+        }
+    }
+
+    /// Range the values of a typical simulation fall into, densities are around the rest density
+    pub fn default_range(&self, params: &SolverParams) -> [f32; 2] {
+        match self {
+            Self::Instance | Self::Speed => [0.0, 2.0],
+            Self::Density => [0.9 * params.rest_density, 1.1 * params.rest_density],
+            // Pressure at the upper end of the density range
+            Self::Pressure => [0.0, 0.1 * params.rest_density * params.stiffness],
+        }
+    }
+}
+
+/// Samples a viridis like gradient, `t` is clamped to 0..1
+pub fn gradient(t: f32) -> Vec3 {
+    const STOPS: [Vec3; 5] = [
+        vec3(0.267, 0.005, 0.329),
+        vec3(0.229, 0.322, 0.546),
+        vec3(0.128, 0.567, 0.551),
+        vec3(0.369, 0.789, 0.383),
+        vec3(0.993, 0.906, 0.144),
+    ];
+
+    let t = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let index = (t as usize).min(STOPS.len() - 2);
+
+    STOPS[index].lerp(STOPS[index + 1], t - index as f32)
+}
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    /// Multiplier on the sprite geometry
+    pub particle_scale: f32,
+    pub render_mode: RenderMode,
+    pub color_map: ColorMap,
+    /// Values mapped to the ends of the gradient
+    pub color_range: [f32; 2],
+}
+
+impl RenderSettings {
+    /// Position of `value` in `color_range`, values past the ends are clamped by [`gradient`]
+    ///
+    /// An empty range splits at its value, so it never divides by zero.
+    pub fn color_position(&self, value: f32) -> f32 {
+        let [low, high] = self.color_range;
+
+        if high == low {
+            return if value < low { 0.0 } else { 1.0 };
+        }
+        (value - low) / (high - low)
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            particle_scale: 1.0,
+            render_mode: RenderMode::Soft,
+            color_map: ColorMap::Instance,
+            color_range: [0.0, 2.0],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RenderUniform {
+    pub particle_scale: f32,
+    pub render_mode: u32,
+    // Uniform buffers are 16 byte aligned
+    _padding: [u32; 2],
+}
+
+impl From<&RenderSettings> for RenderUniform {
+    fn from(settings: &RenderSettings) -> Self {
+        RenderUniform {
+            particle_scale: settings.particle_scale,
+            render_mode: settings.render_mode as u32,
+            _padding: [0; 2],
+        }
+    }
+}
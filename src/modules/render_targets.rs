@@ -0,0 +1,125 @@
+use crate::RenderTexture;
+
+
+#[derive(Debug, Clone)]
+pub struct RenderTargetDescriptor {
+    pub label: String,
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    /// Size relative to the surface, `0.5` allocates at half resolution
+    pub scale: f32,
+}
+
+impl RenderTargetDescriptor {
+    pub fn new(label: &str, format: wgpu::TextureFormat) -> Self {
+        RenderTargetDescriptor {
+            label: label.to_owned(),
+            format,
+            sample_count: 1,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_sample_count(self, sample_count: u32) -> Self {
+        Self { sample_count, ..self }
+    }
+
+    pub fn with_scale(self, scale: f32) -> Self {
+        Self { scale, ..self }
+    }
+
+    fn scaled_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let scale = |size: u32| ((size as f32 * self.scale) as u32).max(1);
+        (scale(width), scale(height))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderTargetId(usize);
+
+
+/// Owns every offscreen target that follows the surface size
+///
+/// Targets are recreated on [`RenderTargetRegistry::resize`], which also bumps
+/// the generation so whoever holds bind groups to them knows to rebuild.
+pub struct RenderTargetRegistry {
+    size: (u32, u32),
+    generation: u64,
+    /// `None` for unregistered targets, so the ids of the others stay valid
+    targets: Vec<Option<(RenderTargetDescriptor, RenderTexture)>>,
+}
+
+impl RenderTargetRegistry {
+    pub fn new(size: (u32, u32)) -> Self {
+        RenderTargetRegistry {
+            size,
+            generation: 0,
+            targets: Vec::new(),
+        }
+    }
+
+    pub fn register(&mut self, device: &wgpu::Device, descriptor: RenderTargetDescriptor) -> RenderTargetId {
+        let texture = Self::create_target(device, &descriptor, self.size);
+        self.targets.push(Some((descriptor, texture)));
+
+        RenderTargetId(self.targets.len() - 1)
+    }
+
+    /// Frees the target, `id` must not be used afterwards
+    pub fn unregister(&mut self, id: RenderTargetId) {
+        self.targets[id.0] = None;
+    }
+
+    pub fn contains(&self, id: RenderTargetId) -> bool {
+        self.targets.get(id.0).is_some_and(Option::is_some)
+    }
+
+    fn create_target(device: &wgpu::Device, descriptor: &RenderTargetDescriptor, size: (u32, u32)) -> RenderTexture {
+        RenderTexture::create_render_texture(
+            device,
+            descriptor.scaled_size(size),
+            descriptor.format,
+            descriptor.sample_count,
+            &descriptor.label,
+        )
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if size == self.size {
+            return;
+        }
+
+        self.size = size;
+        self.generation += 1;
+
+        for (descriptor, texture) in self.targets.iter_mut().flatten() {
+            *texture = Self::create_target(device, descriptor, size);
+        }
+    }
+
+    pub fn get(&self, id: RenderTargetId) -> &RenderTexture {
+        &self.entry(id).1
+    }
+
+    pub fn view(&self, id: RenderTargetId) -> &wgpu::TextureView {
+        &self.get(id).view
+    }
+
+    pub fn descriptor(&self, id: RenderTargetId) -> &RenderTargetDescriptor {
+        &self.entry(id).0
+    }
+
+    fn entry(&self, id: RenderTargetId) -> &(RenderTargetDescriptor, RenderTexture) {
+        self.targets[id.0].as_ref().expect("render target was unregistered")
+    }
+
+    /// Surface size the targets are currently allocated for
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Incremented every time the targets are recreated
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
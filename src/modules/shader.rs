@@ -1,13 +1,18 @@
 use std::{
     fs::read_to_string,
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    fmt,
 };
+use crate::{RendererError, validate_wgsl};
 
 
 pub struct Shader<'a> {
     path: &'a str,
     last_hash: u64,
+    preprocessor: ShaderPreprocessor,
+    processed: ProcessedShader,
     shader_source: wgpu::ShaderSource<'a>,
 }
 
@@ -18,27 +23,445 @@ fn hash_file(file: &String) -> u64 {
 }
 
 impl<'a> Shader<'a> {
-    pub fn new(path: &'a str) -> Self {
-        let shader = read_to_string(path).unwrap();
-        let last_hash = hash_file(&shader);
-        let shader_source = wgpu::ShaderSource::Wgsl(shader.into());
-
-        Shader { 
-            path, 
-            last_hash, 
+    pub fn new(path: &'a str) -> Result<Self, RendererError> {
+        Self::with_preprocessor(path, ShaderPreprocessor::new())
+    }
+
+    /// Preprocesses and validates the file, errors point at the original file and line
+    ///
+    /// wgpu only reports lines of the expanded source, which are off inside includes,
+    /// so the shader is checked with naga and its source map first.
+    pub fn with_preprocessor(path: &'a str, preprocessor: ShaderPreprocessor) -> Result<Self, RendererError> {
+        let processed = preprocessor.process_file(path)?;
+        validate_wgsl(&processed)?;
+        let last_hash = hash_file(&processed.source);
+        let shader_source = wgpu::ShaderSource::Wgsl(processed.source.clone().into());
+
+        Ok(Shader {
+            path,
+            last_hash,
+            preprocessor,
+            processed,
             shader_source
-        }
+        })
     }
 
     /// possibly computation heavy
-    pub fn get_source(&mut self) -> wgpu::ShaderSource {
-        let shader = read_to_string(self.path).unwrap();
-        let hash = hash_file(&shader);
+    pub fn get_source(&mut self) -> Result<wgpu::ShaderSource<'a>, RendererError> {
+        let processed = self.preprocessor.process_file(self.path)?;
+        let hash = hash_file(&processed.source);
 
         if self.last_hash != hash {
-            self.shader_source = wgpu::ShaderSource::Wgsl(shader.into());
+            validate_wgsl(&processed)?;
+            self.last_hash = hash;
+            self.shader_source = wgpu::ShaderSource::Wgsl(processed.source.clone().into());
+            self.processed = processed;
         }
 
+        Ok(self.shader_source.clone())
+    }
+
+    pub fn source(&self) -> wgpu::ShaderSource<'a> {
         self.shader_source.clone()
     }
+
+    /// Output of the last preprocessor run, including its source map
+    pub fn processed(&self) -> &ProcessedShader {
+        &self.processed
+    }
+}
+
+
+/// Original file and line (1-based) a line of preprocessed output came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessErrorKind {
+    Io { path: PathBuf, error: std::io::Error },
+    IncludeCycle { chain: Vec<PathBuf> },
+    MalformedDirective { directive: String },
+    UnknownDirective { directive: String },
+    UnexpectedElse,
+    UnexpectedEndif,
+    UnterminatedConditional,
+}
+
+#[derive(Debug)]
+pub struct PreprocessError {
+    /// Where the error occurred, `None` when the root file itself couldn't be read
+    pub location: Option<SourceLocation>,
+    pub kind: PreprocessErrorKind,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{location}: ")?;
+        }
+
+        match &self.kind {
+            PreprocessErrorKind::Io { path, error } => write!(f, "failed to read {}: {error}", path.display()),
+            PreprocessErrorKind::IncludeCycle { chain } => {
+                let chain = chain.iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ");
+                write!(f, "include cycle: {chain}")
+            }
+            PreprocessErrorKind::MalformedDirective { directive } => write!(f, "malformed directive `{directive}`"),
+            PreprocessErrorKind::UnknownDirective { directive } => write!(f, "unknown directive `#{directive}`"),
+            PreprocessErrorKind::UnexpectedElse => write!(f, "#else without matching #ifdef"),
+            PreprocessErrorKind::UnexpectedEndif => write!(f, "#endif without matching #ifdef"),
+            PreprocessErrorKind::UnterminatedConditional => write!(f, "#ifdef without matching #endif"),
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            PreprocessErrorKind::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+
+/// Values that can be injected into wgsl through `#define`
+pub trait ShaderDefine {
+    fn to_wgsl(&self) -> String;
+}
+
+impl ShaderDefine for f32 {
+    fn to_wgsl(&self) -> String {
+        // Debug formatting always keeps the decimal point, so the literal stays an f32
+        format!("{self:?}")
+    }
+}
+
+impl ShaderDefine for u32 {
+    fn to_wgsl(&self) -> String {
+        format!("{self}u")
+    }
+}
+
+impl ShaderDefine for i32 {
+    fn to_wgsl(&self) -> String {
+        format!("{self}i")
+    }
+}
+
+impl ShaderDefine for bool {
+    fn to_wgsl(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ShaderDefine for &str {
+    fn to_wgsl(&self) -> String {
+        self.to_string()
+    }
+}
+
+
+/// Preprocessed wgsl together with a source map of every output line
+#[derive(Debug, Clone, Default)]
+pub struct ProcessedShader {
+    pub source: String,
+    pub source_map: Vec<SourceLocation>,
+}
+
+impl ProcessedShader {
+    /// Maps a 1-based line of the preprocessed output back to its original location
+    pub fn locate(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1).and_then(|index| self.source_map.get(index))
+    }
+
+    /// Maps a byte offset into the preprocessed output back to its original location
+    pub fn locate_offset(&self, offset: usize) -> Option<&SourceLocation> {
+        let offset = offset.min(self.source.len());
+        let line = self.source[..offset].matches('\n').count() + 1;
+        self.locate(line)
+    }
+}
+
+
+/// Small C-like preprocessor for wgsl
+///
+/// Supports `#include "path"` (relative to the including file, every file is included
+/// at most once), `#define NAME [value]`, `#undef NAME`, `#ifdef NAME`, `#ifndef NAME`,
+/// `#else` and `#endif`. Defines with a value are substituted wherever `NAME` appears
+/// as a whole identifier.
+///
+/// Sources registered with [`ShaderPreprocessor::add_source`] take precedence over
+/// the filesystem, which lets shaders embedded with `include_str!` include each other.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderPreprocessor {
+    defines: HashMap<String, String>,
+    sources: HashMap<PathBuf, String>,
+}
+
+struct Conditional {
+    /// Whether the enclosing block is emitted
+    parent_active: bool,
+    /// Whether the current branch is emitted
+    active: bool,
+    seen_else: bool,
+    location: SourceLocation,
+}
+
+struct ProcessContext<'a> {
+    defines: HashMap<String, String>,
+    sources: &'a HashMap<PathBuf, String>,
+    stack: Vec<PathBuf>,
+    included: HashSet<PathBuf>,
+    output: ProcessedShader,
+}
+
+impl ShaderPreprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines `name` with a value substituted into the shader source
+    pub fn define(&mut self, name: &str, value: impl ShaderDefine) -> &mut Self {
+        self.defines.insert(name.to_owned(), value.to_wgsl());
+        self
+    }
+
+    /// Defines `name` without a value, usable as a feature toggle with `#ifdef`
+    pub fn enable(&mut self, name: &str) -> &mut Self {
+        self.defines.insert(name.to_owned(), String::new());
+        self
+    }
+
+    pub fn undefine(&mut self, name: &str) -> &mut Self {
+        self.defines.remove(name);
+        self
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    /// Registers an in-memory file that can be processed or included as `path`
+    pub fn add_source(&mut self, path: impl AsRef<Path>, source: impl Into<String>) -> &mut Self {
+        self.sources.insert(normalize(path.as_ref()), source.into());
+        self
+    }
+
+    pub fn process_file(&self, path: impl AsRef<Path>) -> Result<ProcessedShader, PreprocessError> {
+        let path = path.as_ref();
+        let source = read_source(&self.sources, path).map_err(|error| PreprocessError {
+            location: None,
+            kind: PreprocessErrorKind::Io { path: path.to_owned(), error },
+        })?;
+
+        self.process_str(path, &source)
+    }
+
+    /// Processes in-memory `source`, `path` is used for includes and the source map
+    pub fn process_str(&self, path: impl AsRef<Path>, source: &str) -> Result<ProcessedShader, PreprocessError> {
+        let mut context = ProcessContext {
+            defines: self.defines.clone(),
+            sources: &self.sources,
+            stack: Vec::new(),
+            included: HashSet::new(),
+            output: ProcessedShader::default(),
+        };
+
+        process_source(&mut context, path.as_ref(), source)?;
+        Ok(context.output)
+    }
+}
+
+/// Lexically resolves `.` and `..` so registered sources can be found by any relative path
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| normalize(path))
+}
+
+fn read_source(sources: &HashMap<PathBuf, String>, path: &Path) -> std::io::Result<String> {
+    match sources.get(&normalize(path)) {
+        Some(source) => Ok(source.clone()),
+        None => read_to_string(path),
+    }
+}
+
+fn process_source(context: &mut ProcessContext<'_>, path: &Path, source: &str) -> Result<(), PreprocessError> {
+    let key = canonical(path);
+    context.stack.push(key.clone());
+    context.included.insert(key);
+
+    let mut conditionals: Vec<Conditional> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let location = SourceLocation { file: path.to_owned(), line: index + 1 };
+        let active = conditionals.last().is_none_or(|c| c.active);
+        let trimmed = line.trim_start();
+
+        let Some(directive) = trimmed.strip_prefix('#') else {
+            if active {
+                let line = substitute_defines(line, &context.defines);
+                context.output.source.push_str(&line);
+                context.output.source.push('\n');
+                context.output.source_map.push(location);
+            }
+            continue;
+        };
+
+        let malformed = || PreprocessError {
+            location: Some(location.clone()),
+            kind: PreprocessErrorKind::MalformedDirective { directive: trimmed.to_owned() },
+        };
+
+        let directive = strip_line_comment(directive).trim();
+        let (name, argument) = directive
+            .split_once(char::is_whitespace)
+            .map(|(name, argument)| (name, argument.trim()))
+            .unwrap_or((directive, ""));
+
+        match name {
+            "ifdef" | "ifndef" => {
+                if argument.is_empty() {
+                    return Err(malformed());
+                }
+                let defined = context.defines.contains_key(argument);
+                conditionals.push(Conditional {
+                    parent_active: active,
+                    active: active && (defined == (name == "ifdef")),
+                    seen_else: false,
+                    location,
+                });
+            }
+            "else" => match conditionals.last_mut() {
+                Some(conditional) if !conditional.seen_else => {
+                    conditional.seen_else = true;
+                    conditional.active = conditional.parent_active && !conditional.active;
+                }
+                _ => return Err(PreprocessError { location: Some(location), kind: PreprocessErrorKind::UnexpectedElse }),
+            },
+            "endif" => {
+                if conditionals.pop().is_none() {
+                    return Err(PreprocessError { location: Some(location), kind: PreprocessErrorKind::UnexpectedEndif });
+                }
+            }
+            _ if !active => {}
+            "define" => {
+                let (define, value) = argument
+                    .split_once(char::is_whitespace)
+                    .map(|(define, value)| (define, value.trim()))
+                    .unwrap_or((argument, ""));
+                if define.is_empty() {
+                    return Err(malformed());
+                }
+                context.defines.insert(define.to_owned(), value.to_owned());
+            }
+            "undef" => {
+                if argument.is_empty() {
+                    return Err(malformed());
+                }
+                context.defines.remove(argument);
+            }
+            "include" => {
+                let include = argument
+                    .strip_prefix('"')
+                    .and_then(|argument| argument.strip_suffix('"'))
+                    .ok_or_else(malformed)?;
+                let include_path = path.parent().unwrap_or(Path::new("")).join(include);
+                let key = canonical(&include_path);
+
+                if context.stack.contains(&key) {
+                    let mut chain = context.stack.clone();
+                    chain.push(key);
+                    return Err(PreprocessError { location: Some(location), kind: PreprocessErrorKind::IncludeCycle { chain } });
+                }
+                if context.included.contains(&key) {
+                    continue;
+                }
+
+                let source = read_source(context.sources, &include_path).map_err(|error| PreprocessError {
+                    location: Some(location.clone()),
+                    kind: PreprocessErrorKind::Io { path: include_path.clone(), error },
+                })?;
+                process_source(context, &include_path, &source)?;
+            }
+            _ => {
+                return Err(PreprocessError {
+                    location: Some(location),
+                    kind: PreprocessErrorKind::UnknownDirective { directive: name.to_owned() },
+                })
+            }
+        }
+    }
+
+    if let Some(conditional) = conditionals.pop() {
+        return Err(PreprocessError { location: Some(conditional.location), kind: PreprocessErrorKind::UnterminatedConditional });
+    }
+
+    context.stack.pop();
+    Ok(())
+}
+
+fn strip_line_comment(line: &str) -> &str {
+    line.split_once("//").map_or(line, |(code, _)| code)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Replaces every whole-identifier occurrence of a define that has a value, comments are kept as they are
+fn substitute_defines(line: &str, defines: &HashMap<String, String>) -> String {
+    if defines.values().all(String::is_empty) {
+        return line.to_owned();
+    }
+
+    let (code, comment) = line.split_at(line.find("//").unwrap_or(line.len()));
+    let mut output = String::with_capacity(line.len());
+    let mut rest = code;
+
+    while let Some(start) = rest.find(|c: char| is_identifier_char(c)) {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find(|c: char| !is_identifier_char(c)).unwrap_or(rest.len());
+        let identifier = &rest[..end];
+
+        match defines.get(identifier) {
+            Some(value) if !value.is_empty() => output.push_str(value),
+            _ => output.push_str(identifier),
+        }
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+    output.push_str(comment);
+    output
 }
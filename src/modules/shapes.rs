@@ -1,4 +1,6 @@
-use crate::Vertex;
+use std::{collections::HashMap, f32::consts::PI};
+use glam::{vec3, Vec3};
+use crate::{Vertex, TriangleMesh};
 
 pub trait Shape {
     const VERTICES: &'static [Vertex];
@@ -14,6 +16,15 @@ pub trait Shape {
 
         vertices
     }
+
+    /// The const tables as runtime geometry, facing +z
+    fn geometry(&self) -> Geometry {
+        Geometry {
+            vertices: Self::VERTICES.to_vec(),
+            normals: vec![Vec3::Z; Self::VERTICES.len()],
+            indices: Indices::U16(Self::INDICES.to_vec()),
+        }
+    }
 }
 
 
@@ -72,3 +83,273 @@ impl Shape for Quad {
 
     const INDICES: &'static [u16] = &[0, 1, 3, 1, 2, 3];
 }
+
+
+
+/// Index buffer contents in either of the formats wgpu supports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Uses 16 bit indices whenever every index fits
+    pub fn compact(indices: Vec<u32>) -> Self {
+        match indices.iter().all(|index| *index <= u16::MAX as u32) {
+            true => Indices::U16(indices.into_iter().map(|index| index as u16).collect()),
+            false => Indices::U32(indices),
+        }
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Indices::U16(_) => wgpu::IndexFormat::Uint16,
+            Indices::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => bytemuck::cast_slice(indices),
+            Indices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            Indices::U16(indices) => indices.iter().map(|index| *index as u32).collect(),
+            Indices::U32(indices) => indices.clone(),
+        }
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+impl From<&[u16]> for Indices {
+    fn from(indices: &[u16]) -> Self {
+        Indices::U16(indices.to_vec())
+    }
+}
+
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        Indices::U32(indices)
+    }
+}
+
+impl From<&[u32]> for Indices {
+    fn from(indices: &[u32]) -> Self {
+        Indices::U32(indices.to_vec())
+    }
+}
+
+
+/// Shapes generated at runtime with a configurable tessellation
+///
+/// Every shape fits in a unit box around the origin like [`Quad`], so `scale` is its
+/// diameter. Triangles wind counter-clockwise seen from outside, flat shapes face +z.
+/// The particle shader masks sprites by their texture coordinates, use
+/// `RenderMode::Square` to draw the solid shapes as particles.
+#[derive(Debug, Clone)]
+pub struct Geometry {
+    pub vertices: Vec<Vertex>,
+    pub normals: Vec<Vec3>,
+    pub indices: Indices,
+}
+
+impl Geometry {
+    fn new(vertices: Vec<Vertex>, normals: Vec<Vec3>, indices: Vec<u32>) -> Self {
+        Geometry { vertices, normals, indices: Indices::compact(indices) }
+    }
+
+    /// Maps a flat shape's xy position to texture coordinates the way the const shapes do
+    fn flat_vertex(x: f32, y: f32) -> Vertex {
+        Vertex {
+            position: [x, y, 0.0],
+            tex_coords: [x + 0.5, 0.5 - y],
+        }
+    }
+
+    /// Regular polygon with a corner at the top, triangulated as a fan from that corner
+    pub fn polygon(sides: u32) -> Self {
+        let sides = sides.max(3);
+        let vertices = (0..sides)
+            .map(|side| {
+                let angle = PI / 2.0 + 2.0 * PI * side as f32 / sides as f32;
+                Self::flat_vertex(0.5 * angle.cos(), 0.5 * angle.sin())
+            })
+            .collect::<Vec<_>>();
+
+        let indices = (1..sides - 1)
+            .flat_map(|side| [0, side, side + 1])
+            .collect();
+
+        Self::new(vertices, vec![Vec3::Z; sides as usize], indices)
+    }
+
+    /// Disc made of `segments` triangles around a center vertex
+    pub fn circle(segments: u32) -> Self {
+        let segments = segments.max(3);
+        let vertices = std::iter::once(Self::flat_vertex(0.0, 0.0))
+            .chain((0..segments).map(|segment| {
+                let angle = 2.0 * PI * segment as f32 / segments as f32;
+                Self::flat_vertex(0.5 * angle.cos(), 0.5 * angle.sin())
+            }))
+            .collect::<Vec<_>>();
+
+        let indices = (0..segments)
+            .flat_map(|segment| [0, segment + 1, (segment + 1) % segments + 1])
+            .collect();
+
+        Self::new(vertices, vec![Vec3::Z; segments as usize + 1], indices)
+    }
+
+    /// Sphere of `segments` slices around y and `rings` stacks from pole to pole
+    pub fn uv_sphere(segments: u32, rings: u32) -> Self {
+        let (segments, rings) = (segments.max(3), rings.max(2));
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+
+        // The seam and pole vertices are duplicated so the texture coordinates don't wrap
+        for ring in 0..=rings {
+            let v = ring as f32 / rings as f32;
+            let polar = PI * v;
+
+            for segment in 0..=segments {
+                let u = segment as f32 / segments as f32;
+                let azimuth = 2.0 * PI * u;
+                let normal = vec3(polar.sin() * azimuth.cos(), polar.cos(), -polar.sin() * azimuth.sin());
+
+                vertices.push(Vertex {
+                    position: (normal * 0.5).to_array(),
+                    tex_coords: [u, v],
+                });
+                normals.push(normal);
+            }
+        }
+
+        let row = segments + 1;
+        let mut indices = Vec::new();
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let top = ring * row + segment;
+                let bottom = top + row;
+
+                if ring != 0 {
+                    indices.extend([top, bottom, top + 1]);
+                }
+                if ring != rings - 1 {
+                    indices.extend([top + 1, bottom, bottom + 1]);
+                }
+            }
+        }
+
+        Self::new(vertices, normals, indices)
+    }
+
+    /// Subdivided icosahedron, 20 * 4^`subdivisions` nearly equal triangles
+    pub fn icosphere(subdivisions: u32) -> Self {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut positions = [
+            (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+            (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+            (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+        ].map(|(x, y, z)| vec3(x, y, z).normalize()).to_vec();
+
+        let mut indices: Vec<u32> = vec![
+            0, 11, 5, 0, 5, 1, 0, 1, 7, 0, 7, 10, 0, 10, 11,
+            1, 5, 9, 5, 11, 4, 11, 10, 2, 10, 7, 6, 7, 1, 8,
+            3, 9, 4, 3, 4, 2, 3, 2, 6, 3, 6, 8, 3, 8, 9,
+            4, 9, 5, 2, 4, 11, 6, 2, 10, 8, 6, 7, 9, 8, 1,
+        ];
+
+        for _ in 0..subdivisions {
+            // Edges are shared by two triangles, both have to use the same midpoint
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    positions.push((positions[a as usize] + positions[b as usize]).normalize());
+                    positions.len() as u32 - 1
+                })
+            };
+
+            indices = indices.chunks_exact(3)
+                .flat_map(|triangle| {
+                    let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [a, ab, ca, b, bc, ab, c, ca, bc, ab, bc, ca]
+                })
+                .collect();
+        }
+
+        let vertices = positions.iter()
+            .map(|normal| Vertex {
+                position: (*normal * 0.5).to_array(),
+                tex_coords: [0.5 + normal.z.atan2(normal.x) / (2.0 * PI), 0.5 - normal.y.asin() / PI],
+            })
+            .collect();
+
+        Self::new(vertices, positions, indices)
+    }
+
+    /// Unit cube with separate vertices per face, so faces stay flat shaded
+    pub fn cube() -> Self {
+        let faces = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+        let mut vertices = Vec::new();
+        let mut normals = Vec::new();
+        let mut indices = Vec::new();
+
+        for normal in faces {
+            let up = if normal.y == 0.0 { Vec3::Y } else { Vec3::Z };
+            let right = up.cross(normal);
+            let first = vertices.len() as u32;
+
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                let position = (normal + right * (2.0 * u - 1.0) + up * (2.0 * v - 1.0)) * 0.5;
+                vertices.push(Vertex {
+                    position: position.to_array(),
+                    tex_coords: [u, 1.0 - v],
+                });
+                normals.push(normal);
+            }
+
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        Self::new(vertices, normals, indices)
+    }
+
+    /// Multiplies every position by `factor`, like [`Shape::scale`]
+    pub fn scale(mut self, factor: f32) -> Self {
+        for vertex in self.vertices.iter_mut() {
+            vertex.position = vertex.position.map(|x| x * factor);
+        }
+
+        self
+    }
+
+    /// For drawing the shape as scene geometry with [`crate::MeshNode`]
+    pub fn to_triangle_mesh(&self) -> TriangleMesh {
+        TriangleMesh {
+            positions: self.vertices.iter().map(|vertex| Vec3::from(vertex.position)).collect(),
+            normals: self.normals.clone(),
+            indices: self.indices.to_u32(),
+        }
+    }
+}
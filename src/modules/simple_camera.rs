@@ -1,5 +1,6 @@
 use glam::{Vec3A, Mat4};
 
+#[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub aspect: f32,
     pub eye: Vec3A,
@@ -40,6 +41,12 @@ pub struct CameraUniform {
     pub view_projection: [[f32; 4]; 4],
 }
 
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,215 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+use crate::{ParticleFrame, ParticleFileError, Checkpoint, CheckpointError};
+
+
+/// Extensions of the files a [`SnapshotCache`] plays, `frcp` being [`Checkpoint`]s
+pub const CACHE_EXTENSIONS: [&str; 4] = ["ply", "csv", "geo", "frcp"];
+
+/// Frame interval used when the frames don't store increasing times, e.g. csv
+pub const DEFAULT_FRAME_INTERVAL: f32 = 1.0 / 30.0;
+
+/// Decoded frames kept around, enough for the two being interpolated and a step back
+const LOADED_FRAMES: usize = 3;
+
+#[derive(Debug)]
+pub enum SnapshotCacheError {
+    Io(io::Error),
+    /// The directory has no file with one of the [`CACHE_EXTENSIONS`]
+    Empty(PathBuf),
+    ParticleFile { path: PathBuf, error: ParticleFileError },
+    Checkpoint { path: PathBuf, error: CheckpointError },
+}
+
+impl fmt::Display for SnapshotCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Empty(directory) => write!(f, "no particle frames found in {}", directory.display()),
+            Self::ParticleFile { path, error } => write!(f, "failed to read {}: {error}", path.display()),
+            Self::Checkpoint { path, error } => write!(f, "failed to read {}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::ParticleFile { error, .. } => Some(error),
+            Self::Checkpoint { error, .. } => Some(error),
+            Self::Empty(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotCacheError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+
+/// A directory of precomputed frames played back instead of running the solver
+///
+/// Frames are grouped by the name before the number at the end of their file name and
+/// ordered by that number, so both `name_00012.ply` and houdini's unpadded `name.12.geo`
+/// sequences work, and several sequences in one directory play one after the other.
+/// Only the frames around `current` are decoded, the rest stays on disk.
+#[derive(Debug, Clone)]
+pub struct SnapshotCache {
+    pub directory: PathBuf,
+    pub frames: Vec<PathBuf>,
+    /// Simulated seconds between two frames, taken from the times of the first two
+    pub frame_interval: f32,
+    /// Start over after the last frame instead of pausing on it
+    pub looping: bool,
+    current: usize,
+    loaded: Vec<(usize, ParticleFrame)>,
+}
+
+impl SnapshotCache {
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self, SnapshotCacheError> {
+        let directory = directory.into();
+        let mut frames = std::fs::read_dir(&directory)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| CACHE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+            })
+            .collect::<Vec<_>>();
+        frames.sort_by_cached_key(|path| sequence_key(path));
+
+        if frames.is_empty() {
+            return Err(SnapshotCacheError::Empty(directory));
+        }
+
+        let mut cache = SnapshotCache {
+            directory,
+            frames,
+            frame_interval: DEFAULT_FRAME_INTERVAL,
+            looping: true,
+            current: 0,
+            loaded: Vec::new(),
+        };
+        if cache.len() > 1 {
+            let interval = cache.frame(1)?.time - cache.frame(0)?.time;
+            if interval > 0.0 {
+                cache.frame_interval = interval;
+            }
+        }
+
+        Ok(cache)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Jumps to `index`, clamped to the last frame
+    pub fn seek(&mut self, index: usize) {
+        self.current = index.min(self.len().saturating_sub(1));
+    }
+
+    /// Moves `frames` forward, returns false when it stopped on the last frame because it isn't looping
+    pub fn advance(&mut self, frames: u32) -> bool {
+        let target = self.current + frames as usize;
+
+        match (target < self.len(), self.looping) {
+            (true, _) => self.current = target,
+            (false, true) => self.current = target % self.len(),
+            (false, false) => {
+                self.current = self.len() - 1;
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Moves one frame back, wrapping around to the last frame when looping
+    pub fn step_back(&mut self) {
+        self.current = match (self.current, self.looping) {
+            (0, true) => self.len() - 1,
+            (current, _) => current.saturating_sub(1),
+        };
+    }
+
+    /// Decodes the frame at `index`, or returns it from the frames already loaded
+    ///
+    /// Only a few frames stay decoded, the least recently used one makes room for the next.
+    pub fn frame(&mut self, index: usize) -> Result<&ParticleFrame, SnapshotCacheError> {
+        // Most recently used last
+        match self.loaded.iter().position(|(loaded, _)| *loaded == index) {
+            Some(position) => {
+                let loaded = self.loaded.remove(position);
+                self.loaded.push(loaded);
+            }
+            None => {
+                let frame = load_frame(&self.frames[index])?;
+                if self.loaded.len() >= LOADED_FRAMES {
+                    self.loaded.remove(0);
+                }
+                self.loaded.push((index, frame));
+            }
+        }
+
+        Ok(&self.loaded[self.loaded.len() - 1].1)
+    }
+
+    /// The current frame, `alpha` of the way to the next one
+    ///
+    /// The last frame isn't blended into the first when looping, particles would
+    /// sweep across the scene instead of jumping back.
+    pub fn sample(&mut self, alpha: f32) -> Result<ParticleFrame, SnapshotCacheError> {
+        let current = self.frame(self.current)?.clone();
+        let next = self.current + 1;
+
+        match alpha > 0.0 && next < self.len() {
+            true => Ok(current.lerp(self.frame(next)?, alpha)),
+            false => Ok(current),
+        }
+    }
+}
+
+/// Reads a frame in any of the [`CACHE_EXTENSIONS`]
+fn load_frame(path: &Path) -> Result<ParticleFrame, SnapshotCacheError> {
+    let is_checkpoint = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("frcp"));
+
+    match is_checkpoint {
+        true => Checkpoint::load(path)
+            .map(|checkpoint| ParticleFrame::from(&checkpoint.particles))
+            .map_err(|error| SnapshotCacheError::Checkpoint { path: path.to_owned(), error }),
+        false => ParticleFrame::load(path)
+            .map_err(|error| SnapshotCacheError::ParticleFile { path: path.to_owned(), error }),
+    }
+}
+
+/// Name of the sequence the file belongs to and its number in it, files without a number come first
+fn sequence_key(path: &Path) -> (String, Option<u64>, PathBuf) {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+    let (name, number) = match split_frame_number(stem) {
+        Some((name, number)) => (name, Some(number)),
+        None => (stem, None),
+    };
+
+    (name.to_owned(), number, path.to_owned())
+}
+
+/// Splits a file stem into the name and the frame number at its end, `name.0012` into `name.` and 12
+pub(crate) fn split_frame_number(stem: &str) -> Option<(&str, u64)> {
+    let name = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+
+    Some((name, stem[name.len()..].parse().ok()?))
+}
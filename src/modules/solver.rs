@@ -0,0 +1,229 @@
+use std::{collections::HashMap, f32::consts::PI, time::{Duration, Instant}};
+use glam::{IVec3, Vec3};
+use crate::Instance;
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct SolverParams {
+    /// Seconds advanced by a single step
+    pub timestep: f32,
+    /// Pressure per unit of density above the rest density
+    pub stiffness: f32,
+    pub viscosity: f32,
+    pub gravity: Vec3,
+    pub rest_density: f32,
+    /// Distance particles were spawned at, sets the particle mass
+    pub particle_spacing: f32,
+    /// Kernel radius, also the neighbor search cell size
+    pub smoothing_radius: f32,
+    /// Velocity kept when bouncing off the bounds
+    pub restitution: f32,
+    pub bounds_min: Vec3,
+    pub bounds_max: Vec3,
+}
+
+impl Default for SolverParams {
+    fn default() -> Self {
+        SolverParams {
+            timestep: 1.0 / 240.0,
+            stiffness: 20.0,
+            viscosity: 0.02,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            rest_density: 1000.0,
+            particle_spacing: 0.1,
+            smoothing_radius: 0.2,
+            restitution: 0.5,
+            bounds_min: Vec3::new(-2.0, -1.0, -3.0),
+            bounds_max: Vec3::new(2.0, 3.0, 1.0),
+        }
+    }
+}
+
+impl SolverParams {
+    pub fn particle_mass(&self) -> f32 {
+        self.rest_density * self.particle_spacing.powi(3)
+    }
+}
+
+
+/// Cpu time spent in the stages of a step
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepTimings {
+    pub neighbor_search: Duration,
+    /// Density, force and integration passes
+    pub simulation: Duration,
+}
+
+
+/// Weakly compressible sph, positions are taken from the instances it steps
+///
+/// The remaining per-particle attributes live here, indexed like the instances.
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    pub params: SolverParams,
+    pub velocities: Vec<Vec3>,
+    pub densities: Vec<f32>,
+    pub pressures: Vec<f32>,
+    /// Simulated time in seconds
+    pub time: f32,
+    pub steps: u64,
+    grid: HashMap<IVec3, Vec<usize>>,
+}
+
+impl Solver {
+    pub fn new(params: SolverParams, particle_count: usize) -> Self {
+        let mut solver = Solver { params, ..Default::default() };
+        solver.reset(particle_count);
+
+        solver
+    }
+
+    /// Puts every particle at rest and the time back to zero
+    pub fn reset(&mut self, particle_count: usize) {
+        self.velocities = vec![Vec3::ZERO; particle_count];
+        self.densities = vec![self.params.rest_density; particle_count];
+        self.pressures = vec![0.0; particle_count];
+        self.time = 0.0;
+        self.steps = 0;
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.velocities.len()
+    }
+
+    fn cell(&self, position: Vec3) -> IVec3 {
+        (position / self.params.smoothing_radius).floor().as_ivec3()
+    }
+
+    /// Buckets particles into cells of the smoothing radius
+    pub fn find_neighbors(&mut self, instances: &[Instance]) {
+        for cell in self.grid.values_mut() {
+            cell.clear();
+        }
+
+        for (index, instance) in instances.iter().enumerate() {
+            let cell = self.cell(instance.position);
+            self.grid.entry(cell).or_default().push(index);
+        }
+    }
+
+    /// Calls `f` with every particle within the smoothing radius of `position`, itself included
+    fn for_each_neighbor(&self, instances: &[Instance], position: Vec3, mut f: impl FnMut(usize, Vec3, f32)) {
+        let cell = self.cell(position);
+        let radius_squared = self.params.smoothing_radius.powi(2);
+
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    let Some(neighbors) = self.grid.get(&(cell + IVec3::new(x, y, z))) else {
+                        continue;
+                    };
+
+                    for &neighbor in neighbors {
+                        let offset = position - instances[neighbor].position;
+                        let distance_squared = offset.length_squared();
+                        if distance_squared < radius_squared {
+                            f(neighbor, offset, distance_squared);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn compute_densities(&mut self, instances: &[Instance]) {
+        let h = self.params.smoothing_radius;
+        let poly6 = 315.0 / (64.0 * PI * h.powi(9));
+        let mass = self.params.particle_mass();
+
+        let densities = instances.iter()
+            .map(|instance| {
+                let mut density = 0.0;
+                self.for_each_neighbor(instances, instance.position, |_, _, distance_squared| {
+                    density += mass * poly6 * (h * h - distance_squared).powi(3);
+                });
+                density
+            })
+            .collect();
+        self.densities = densities;
+
+        // Clamped so particles never pull on each other
+        self.pressures = self.densities.iter()
+            .map(|density| (self.params.stiffness * (density - self.params.rest_density)).max(0.0))
+            .collect();
+    }
+
+    pub fn integrate(&mut self, instances: &mut [Instance], dt: f32) {
+        let params = self.params;
+        let h = params.smoothing_radius;
+        let spiky = -45.0 / (PI * h.powi(6));
+        let viscosity_laplacian = 45.0 / (PI * h.powi(6));
+        let mass = params.particle_mass();
+
+        let accelerations = (0..instances.len())
+            .map(|index| {
+                let density = self.densities[index];
+                let pressure_term = self.pressures[index] / (density * density);
+                let mut acceleration = params.gravity;
+
+                self.for_each_neighbor(instances, instances[index].position, |neighbor, offset, distance_squared| {
+                    if neighbor == index || distance_squared == 0.0 {
+                        return;
+                    }
+
+                    let distance = distance_squared.sqrt();
+                    let neighbor_density = self.densities[neighbor];
+                    let neighbor_pressure_term = self.pressures[neighbor] / (neighbor_density * neighbor_density);
+                    let gradient = offset / distance * spiky * (h - distance).powi(2);
+
+                    acceleration -= mass * (pressure_term + neighbor_pressure_term) * gradient;
+                    acceleration += params.viscosity * mass * (self.velocities[neighbor] - self.velocities[index])
+                        / neighbor_density * viscosity_laplacian * (h - distance) / density;
+                });
+
+                acceleration
+            })
+            .collect::<Vec<_>>();
+
+        for ((instance, velocity), acceleration) in instances.iter_mut().zip(self.velocities.iter_mut()).zip(accelerations) {
+            *velocity += acceleration * dt;
+            instance.position += *velocity * dt;
+
+            for axis in 0..3 {
+                if instance.position[axis] < params.bounds_min[axis] {
+                    instance.position[axis] = params.bounds_min[axis];
+                    velocity[axis] = -velocity[axis] * params.restitution;
+                } else if instance.position[axis] > params.bounds_max[axis] {
+                    instance.position[axis] = params.bounds_max[axis];
+                    velocity[axis] = -velocity[axis] * params.restitution;
+                }
+            }
+        }
+    }
+
+    /// Advances the simulation by one timestep
+    pub fn step(&mut self, instances: &mut [Instance]) -> StepTimings {
+        self.substep(instances, self.params.timestep)
+    }
+
+    /// Advances the simulation by `dt` instead of the configured timestep
+    pub fn substep(&mut self, instances: &mut [Instance], dt: f32) -> StepTimings {
+        if instances.len() != self.particle_count() {
+            self.reset(instances.len());
+        }
+
+        let start = Instant::now();
+        self.find_neighbors(instances);
+        let neighbor_search = start.elapsed();
+
+        let start = Instant::now();
+        self.compute_densities(instances);
+        self.integrate(instances, dt);
+        let simulation = start.elapsed();
+
+        self.time += dt;
+        self.steps += 1;
+
+        StepTimings { neighbor_search, simulation }
+    }
+}
@@ -1,13 +1,21 @@
-use std::{iter, time::Instant};
+use std::{iter, path::PathBuf, time::Instant};
 use wgpu::util::DeviceExt;
 use winit::{
     window::Window,
-    event::WindowEvent,
+    event::{WindowEvent, KeyboardInput, ElementState, VirtualKeyCode},
 };
 use crate::{
-    Vertex, 
+    Vertex, Indices,
     Instance, InstanceRaw,
-    Camera, CameraUniform, DepthTexture
+    Camera, CameraUniform, DepthTexture,
+    PostProcess, HDR_FORMAT,
+    RenderTargetRegistry, RenderTargetDescriptor, RenderTargetId,
+    RenderGraph, RenderNode, Overlay, ParticleNode, PostProcessNode,
+    RendererError, StateBuilder, StateSettings,
+    Solver, RenderSettings, RenderUniform, gradient,
+    Profiler, Playback, StepTimings, ParticleFrame, SnapshotCache,
+    FrameCapture, CapturedFrame, Recorder, RecorderError,
+    HDR, DEPTH, MSAA,
 };
 
 
@@ -19,24 +27,37 @@ pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
 };
 
 pub struct State {
-    pub surface: wgpu::Surface,
+    /// `None` for a headless state, frames are then drawn into an offscreen texture
+    pub surface: Option<wgpu::Surface>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    /// Size and format of the frames, also used without a surface
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub window: Window,
+    pub window: Option<Window>,
     pub surface_format: wgpu::TextureFormat,
+    pub clear_color: wgpu::Color,
     
     pub render_pipeline: wgpu::RenderPipeline,
-    pub depth_texture: DepthTexture,
+    pub sample_count: u32,
+    /// Offscreen targets, recreated together with the surface on resize
+    pub render_targets: RenderTargetRegistry,
+    /// `None` when rendering without msaa, resolves into `post_process.hdr_target`
+    pub msaa_target: Option<RenderTargetId>,
+    pub depth_target: RenderTargetId,
+    pub post_process: PostProcess,
+    /// Passes recorded every frame, see [`State::add_render_node`]
+    pub render_graph: RenderGraph,
     
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_indices: u32,
+    pub index_format: wgpu::IndexFormat,
    
     pub instances: Vec<Instance>,
+    /// Positions before the last fixed step, rendering interpolates from them
+    pub previous_positions: Vec<glam::Vec3>,
     pub num_instances: u32,
-    #[allow(dead_code)]
     instance_buffer: wgpu::Buffer,
 
     pub camera: Camera,
@@ -44,16 +65,50 @@ pub struct State {
     pub camera_buffer: wgpu::Buffer,
     pub camera_bind_group: wgpu::BindGroup,
 
+    pub render_settings: RenderSettings,
+    pub render_settings_buffer: wgpu::Buffer,
+    pub render_settings_bind_group: wgpu::BindGroup,
+
+    pub solver: Solver,
+    pub profiler: Profiler,
+    pub playback: Playback,
+    /// Frames played back instead of simulating, see [`State::open_cache`]
+    pub cache: Option<SnapshotCache>,
+    /// Cache frame the instances hold without interpolation, paused playback skips reloading it
+    shown_cache_frame: Option<usize>,
+    /// Gets every rendered frame while recording, without the overlays
+    pub recorder: Option<Recorder>,
+    capture: Option<FrameCapture>,
+
     pub start: Instant,
 }
 
 impl State {
-    async fn init_wgpu(window: &Window) -> (wgpu::Surface, wgpu::TextureFormat, wgpu::Device, wgpu::Queue, wgpu::SurfaceConfiguration, winit::dpi::PhysicalSize<u32>) {
-        let size = window.inner_size();
+    /// Highest sample count up to `requested` that both the color and depth format support
+    fn choose_sample_count(adapter: &wgpu::Adapter, format: wgpu::TextureFormat, adapter_specific: bool, requested: u32) -> u32 {
+        let format_flags = |format: wgpu::TextureFormat| match adapter_specific {
+            true => adapter.get_texture_format_features(format).flags,
+            false => format.describe().guaranteed_format_features.flags,
+        };
+        let color_flags = format_flags(format);
+        let depth_flags = format_flags(DepthTexture::DEPTH_FORMAT);
 
+        let sample_count = [8, 4, 2, 1].into_iter()
+            .filter(|count| *count <= requested)
+            .find(|count| color_flags.sample_count_supported(*count) && depth_flags.sample_count_supported(*count))
+            .unwrap_or(1);
+
+        if sample_count != requested {
+            log::warn!("{requested}x msaa is not supported for {format:?}, falling back to {sample_count}x");
+        }
+
+        sample_count
+    }
+
+    async fn init_wgpu(window: Option<&Window>, size: winit::dpi::PhysicalSize<u32>, settings: &StateSettings) -> Result<(Option<wgpu::Surface>, wgpu::TextureFormat, wgpu::Device, wgpu::Queue, wgpu::SurfaceConfiguration, winit::dpi::PhysicalSize<u32>, u32), RendererError> {
         // The instance is a handle to our GPU
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
+            backends: settings.backends,
             dx12_shader_compiler: Default::default(),
         });
         
@@ -61,58 +116,94 @@ impl State {
         //
         // The surface needs to live as long as the window that created it.
         // State owns the window so this should be safe.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        let surface = window.map(|window| unsafe { instance.create_surface(window) }).transpose()?;
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                power_preference: settings.power_preference,
+                compatible_surface: surface.as_ref(),
+                force_fallback_adapter: settings.force_fallback_adapter,
             })
             .await
-            .unwrap();
+            .ok_or(RendererError::NoAdapter)?;
+
+        let surface_caps = surface.as_ref().map(|surface| surface.get_capabilities(&adapter));
+
+        // Srgb surfaces, you'll need to account for that when drawing to the frame.
+        let surface_format = match &surface_caps {
+            Some(surface_caps) => surface_caps.formats.iter()
+                .copied()
+                .find(|f| f.describe().srgb == settings.prefer_srgb)
+                .unwrap_or(surface_caps.formats[0]),
+            // Headless frames are read back as rgba, so no channels need swapping
+            None if settings.prefer_srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            None => wgpu::TextureFormat::Rgba8Unorm,
+        };
+
+        // Without adapter specific format features only 1x and 4x msaa are guaranteed
+        let mut features = settings.features;
+        let adapter_specific = settings.sample_count > 1
+            && adapter.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        if adapter_specific {
+            features |= wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+        }
+        let sample_count = Self::choose_sample_count(&adapter, HDR_FORMAT, adapter_specific, settings.sample_count);
+
+        if settings.gpu_timing && adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            features |= wgpu::Features::TIMESTAMP_QUERY;
+        }
 
         // list of supported features can be fetched by calling adapter.get_features
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
-                    // WebGL doesn't support all of wgpu's features, so if
-                    // we're building for the web we'll have to disable some.
-                    limits: wgpu::Limits::default(),
+                    features,
+                    limits: settings.limits.clone(),
                 },
                 None, // Trace path
             )
-            .await
-            .unwrap();
-
-        let surface_caps = surface.get_capabilities(&adapter);
-
-        // Srgb surfaces, you'll need to account for that when drawing to the frame.
-        let surface_format = surface_caps.formats.iter()
-            .copied()
-            .filter(|f| f.describe().srgb)
-            .next()
-            .unwrap_or(surface_caps.formats[0]);
+            .await?;
+
+        let (present_mode, alpha_mode) = match &surface_caps {
+            Some(surface_caps) => {
+                let present_mode = match settings.present_mode {
+                    // The auto modes are always supported, they fall back on their own
+                    Some(mode @ (wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync)) => mode,
+                    Some(mode) if surface_caps.present_modes.contains(&mode) => mode,
+                    Some(mode) => {
+                        log::warn!("Present mode {mode:?} is not supported, falling back to {:?}", surface_caps.present_modes[0]);
+                        surface_caps.present_modes[0]
+                    }
+                    None => surface_caps.present_modes[0],
+                };
+                (present_mode, surface_caps.alpha_modes[0])
+            }
+            None => (wgpu::PresentMode::Fifo, wgpu::CompositeAlphaMode::Opaque),
+        };
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | surface_copy_usage(adapter.get_info().backend),
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
+            present_mode,
+            alpha_mode,
             view_formats: vec![],
         };
 
-        surface.configure(&device, &config);
-        (surface, surface_format, device, queue, config, size)
+        if let Some(surface) = &surface {
+            surface.configure(&device, &config);
+        }
+        Ok((surface, surface_format, device, queue, config, size, sample_count))
     }
 
 
-    fn init_render_pipeline(device: &wgpu::Device, source: wgpu::ShaderSource, config: &wgpu::SurfaceConfiguration, camera_bind_group_layout: &wgpu::BindGroupLayout) 
-        -> wgpu::RenderPipeline {
+    async fn init_render_pipeline(device: &wgpu::Device, source: wgpu::ShaderSource<'_>, sample_count: u32, camera_bind_group_layout: &wgpu::BindGroupLayout, render_settings_bind_group_layout: &wgpu::BindGroupLayout) 
+        -> Result<wgpu::RenderPipeline, RendererError> {
+        // Without an error scope an invalid shader panics inside wgpu
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source,
@@ -122,7 +213,7 @@ impl State {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[camera_bind_group_layout],
+                bind_group_layouts: &[camera_bind_group_layout, render_settings_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -138,7 +229,7 @@ impl State {
                 module: &shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
+                    format: HDR_FORMAT,
                     blend: Some(wgpu::BlendState {
                         color: wgpu::BlendComponent::REPLACE,
                         alpha: wgpu::BlendComponent::OVER,
@@ -167,7 +258,7 @@ impl State {
                 stencil: wgpu::StencilState::default()
             }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: true,
             },
@@ -176,7 +267,10 @@ impl State {
             multiview: None,
         });
 
-        render_pipeline
+        match device.pop_error_scope().await {
+            Some(error) => Err(RendererError::ShaderCompile(error.to_string())),
+            None => Ok(render_pipeline),
+        }
     }
 
 
@@ -218,7 +312,42 @@ impl State {
     }
 
 
-    fn init_buffers(device: &wgpu::Device, vertices: &[Vertex], indices: &[u16], instances: &Vec<Instance>)
+    fn init_render_settings(settings: &RenderSettings, device: &wgpu::Device) -> (wgpu::Buffer, wgpu::BindGroup, wgpu::BindGroupLayout) {
+        let render_settings_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Render Settings Buffer"),
+            contents: bytemuck::cast_slice(&[RenderUniform::from(settings)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let render_settings_bind_group_layout = 
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { 
+                        ty: wgpu::BufferBindingType::Uniform, 
+                        has_dynamic_offset: false, 
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("Render settings bind group layout"),
+            });
+
+        let render_settings_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &render_settings_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: render_settings_buffer.as_entire_binding(),
+            }],
+            label: Some("render_settings_bind_group"),
+        });
+
+        (render_settings_buffer, render_settings_bind_group, render_settings_bind_group_layout)
+    }
+
+
+    fn init_buffers(device: &wgpu::Device, vertices: &[Vertex], indices: &Indices, instances: &[Instance])
         -> (wgpu::Buffer, wgpu::Buffer, u32, wgpu::Buffer) {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
@@ -227,7 +356,7 @@ impl State {
         });
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(indices),
+            contents: indices.as_bytes(),
             usage: wgpu::BufferUsages::INDEX,
         });
         let num_indices = indices.len() as u32;
@@ -245,28 +374,67 @@ impl State {
     }
 
 
-    pub async fn new<'a>(window: Window, shader_source: wgpu::ShaderSource<'a>, vertices: &[Vertex], indices: &[u16], instances: Vec<Instance>, camera: Camera) -> Self {
-        let (surface, surface_format, device, queue, config, size) = Self::init_wgpu(&window).await;
+    pub fn builder(window: Window, shader_source: wgpu::ShaderSource<'_>) -> StateBuilder<'_> {
+        StateBuilder::new(window, shader_source)
+    }
+
+    /// A state without window that renders `size` frames offscreen, read them with [`State::capture_frame`]
+    pub fn headless_builder(shader_source: wgpu::ShaderSource<'_>, size: (u32, u32)) -> StateBuilder<'_> {
+        StateBuilder::headless(shader_source, size)
+    }
+
+    pub async fn new(window: Window, shader_source: wgpu::ShaderSource<'_>, vertices: &[Vertex], indices: impl Into<Indices>, instances: Vec<Instance>, camera: Camera) -> Result<Self, RendererError> {
+        let size = window.inner_size();
+        Self::from_settings(Some(window), size, shader_source, vertices, &indices.into(), instances, camera, StateSettings::default()).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn from_settings(window: Option<Window>, size: winit::dpi::PhysicalSize<u32>, shader_source: wgpu::ShaderSource<'_>, vertices: &[Vertex], indices: &Indices, instances: Vec<Instance>, camera: Camera, settings: StateSettings) -> Result<Self, RendererError> {
+        let (surface, surface_format, device, queue, config, size, sample_count) = Self::init_wgpu(window.as_ref(), size, &settings).await?;
         let (camera_uniform, camera_buffer, camera_bind_group, camera_bind_group_layout) = Self::init_camera(&camera, &device);
-        let render_pipeline = Self::init_render_pipeline(&device, shader_source, &config, &camera_bind_group_layout);
+        let (render_settings_buffer, render_settings_bind_group, render_settings_bind_group_layout) = Self::init_render_settings(&settings.render, &device);
+        let render_pipeline = Self::init_render_pipeline(&device, shader_source, sample_count, &camera_bind_group_layout, &render_settings_bind_group_layout).await?;
         let (vertex_buffer, index_buffer, num_indices, instance_buffer) = Self::init_buffers(&device, vertices, indices, &instances);
         let num_instances = instances.len() as _;
+        let solver = Solver::new(settings.solver, instances.len());
+        let profiler = Profiler::new(&device, &queue);
         let start = Instant::now();
-        let depth_texture = DepthTexture::create_depth_texture(&device, &config, "depth_texture");
+        let mut render_targets = RenderTargetRegistry::new((config.width, config.height));
+        let depth_target = render_targets.register(&device, RenderTargetDescriptor::new("depth_texture", DepthTexture::DEPTH_FORMAT).with_sample_count(sample_count));
+        let msaa_target = (sample_count > 1)
+            .then(|| render_targets.register(&device, RenderTargetDescriptor::new("msaa_texture", HDR_FORMAT).with_sample_count(sample_count)));
+        let post_process = PostProcess::new(&device, config.format, &mut render_targets, settings.post_process)?;
+
+        let mut render_graph = RenderGraph::new();
+        render_graph.import(HDR, post_process.hdr_target);
+        render_graph.import(DEPTH, depth_target);
+        if let Some(msaa_target) = msaa_target {
+            render_graph.import(MSAA, msaa_target);
+        }
+        render_graph.add_node(ParticleNode, &mut render_targets, &device)?;
+        render_graph.add_node(PostProcessNode, &mut render_targets, &device)?;
 
-        State {
+        Ok(State {
             surface,
             device,
             queue,
             config,
             size,
             render_pipeline,
-            depth_texture,
+            sample_count,
+            render_targets,
+            msaa_target,
+            depth_target,
+            post_process,
+            render_graph,
             window,
             surface_format,
+            clear_color: settings.clear_color,
             vertex_buffer,
             index_buffer,
             num_indices,
+            index_format: indices.format(),
+            previous_positions: Vec::new(),
             instances,
             num_instances,
             instance_buffer,
@@ -275,22 +443,49 @@ impl State {
             camera_uniform,
             camera_buffer,
             camera_bind_group,
-        }
+            render_settings: settings.render,
+            render_settings_buffer,
+            render_settings_bind_group,
+            solver,
+            profiler,
+            playback: Playback::default(),
+            cache: None,
+            shown_cache_frame: None,
+            recorder: None,
+            capture: None,
+        })
     }
 }
 
 
 impl State {
+    /// Uploads the instances, colored by `render_settings.color_map`
     pub fn update_instances(&mut self) {
+        let color_map = self.render_settings.color_map;
+        let alpha = self.playback.interpolation_alpha(self.solver.params.timestep);
+        let interpolate = alpha < 1.0 && self.previous_positions.len() == self.instances.len();
+
         let raw_instances = self.instances.iter()
-            .map(|instance| instance.to_raw())
+            .enumerate()
+            .map(|(index, instance)| {
+                let mut raw = instance.to_raw();
+                if interpolate {
+                    raw.position = self.previous_positions[index].lerp(instance.position, alpha).into();
+                }
+                if let Some(value) = color_map.value(&self.solver, index) {
+                    raw.color = gradient(self.render_settings.color_position(value)).into();
+                }
+                raw
+            })
             .collect::<Vec<_>>();
 
         self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&raw_instances));
     }
 
     pub fn resize_instances(&mut self, instances: Vec<Instance>) {
+        self.shown_cache_frame = None;
         self.instances = instances;
+        self.num_instances = self.instances.len() as _;
         let raw_instances = self.instances.iter()
             .map(|instance| instance.to_raw())
             .collect::<Vec<_>>();
@@ -304,16 +499,71 @@ impl State {
         self.instance_buffer = instance_buffer;
     }
 
+    pub fn instance_buffer(&self) -> &wgpu::Buffer {
+        &self.instance_buffer
+    }
+
+    /// The particles with their solver attributes, for exporting
+    pub fn particle_frame(&self) -> ParticleFrame {
+        ParticleFrame::from_instances(&self.instances).with_solver(&self.solver)
+    }
+
+    /// Replaces the particles and solver state with `frame`, e.g. a snapshot loaded as initial condition
+    pub fn load_frame(&mut self, frame: &ParticleFrame) {
+        self.resize_instances(frame.to_instances());
+        frame.restore_solver(&mut self.solver);
+        self.previous_positions.clear();
+        self.playback.history.clear();
+    }
+
+    /// Shows `frame` without touching the rewind history, the instance buffer is only
+    /// recreated when the particle count changes
+    pub fn show_frame(&mut self, frame: &ParticleFrame) {
+        self.shown_cache_frame = None;
+        match frame.len() == self.instances.len() {
+            true => self.instances = frame.to_instances(),
+            false => self.resize_instances(frame.to_instances()),
+        }
+        frame.restore_solver(&mut self.solver);
+        self.previous_positions.clear();
+    }
+
+    /// Plays the frames in `directory` instead of running the solver, the playback
+    /// controls then pause, step, rewind and speed up the cache
+    pub fn open_cache(&mut self, directory: impl Into<PathBuf>) -> Result<(), RendererError> {
+        let mut cache = SnapshotCache::open(directory)?;
+        let frame = cache.sample(0.0)?;
+        self.show_frame(&frame);
+        self.shown_cache_frame = Some(cache.current());
+        self.playback.history.clear();
+        self.cache = Some(cache);
+
+        Ok(())
+    }
+
+    /// Goes back to simulating, starting from the frame that was shown
+    pub fn close_cache(&mut self) {
+        self.cache = None;
+        self.shown_cache_frame = None;
+    }
+
     pub fn update_camera(&mut self) {
         self.camera_uniform.update_view_projection(&self.camera);
         self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
     }
+
+    pub fn update_render_settings(&mut self) {
+        self.queue.write_buffer(&self.render_settings_buffer, 0, bytemuck::cast_slice(&[RenderUniform::from(&self.render_settings)]));
+    }
 }
 
 
 impl State {
+    /// # Panics
+    ///
+    /// When the state is headless, see [`State::headless_builder`]
     pub fn window(&self) -> &Window {
-        &self.window
+        self.window.as_ref().expect("a headless state has no window")
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -321,17 +571,40 @@ impl State {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth_texture = DepthTexture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+            self.render_targets.resize(&self.device, (new_size.width, new_size.height));
+            self.post_process.resize(&self.device, &self.render_targets);
 
             self.camera.aspect = self.config.width as f32 / self.config.height as f32;
             self.update_camera();
         }
     }
 
-    #[allow(unused_variables)]
+    /// Playback shortcuts: space pauses, right steps, left rewinds, up and down change the speed
     pub fn input(&mut self, event: &WindowEvent) -> bool {
-        false
+        let WindowEvent::KeyboardInput {
+            input: KeyboardInput {
+                state: ElementState::Pressed,
+                virtual_keycode: Some(key),
+                ..
+            },
+            ..
+        } = event else {
+            return false;
+        };
+
+        match key {
+            VirtualKeyCode::Space => self.playback.toggle_pause(),
+            VirtualKeyCode::Right => self.playback.step(),
+            VirtualKeyCode::Left => self.rewind(),
+            VirtualKeyCode::Up => self.playback.change_speed(true),
+            VirtualKeyCode::Down => self.playback.change_speed(false),
+            _ => return false,
+        }
+
+        true
     }
 
     pub fn update(&mut self) { 
@@ -340,102 +613,229 @@ impl State {
         // if num_elapsed <= self.instances.len() as _ {
         //     self.num_instances = num_elapsed;
         // }
+        if self.cache.is_some() {
+            self.update_cache();
+        } else {
+            self.update_solver();
+        }
+
+        let upload = Instant::now();
         self.update_instances();
+        self.update_camera();
+        self.update_render_settings();
+        self.post_process.update(&self.queue);
+        self.profiler.record_cpu("upload", upload.elapsed());
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    /// Steps back through the rewind history, or a frame of the cache
+    pub fn rewind(&mut self) {
+        match &mut self.cache {
+            Some(cache) => {
+                self.playback.paused = true;
+                cache.step_back();
+                self.show_cached_frame(0.0);
+            }
+            None => {
+                self.playback.rewind(&mut self.instances, &mut self.solver);
+            }
+        }
+    }
 
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
+    fn update_solver(&mut self) {
+        let timestep = self.solver.params.timestep;
+        let substeps = self.playback.substeps.max(1);
+        let steps = self.advance_playback(timestep);
+
+        let mut timings = StepTimings::default();
+        for _ in 0..steps {
+            self.playback.record(&self.instances, &self.solver);
+            self.previous_positions.clear();
+            self.previous_positions.extend(self.instances.iter().map(|instance| instance.position));
+
+            for _ in 0..substeps {
+                let step = self.solver.substep(&mut self.instances, timestep / substeps as f32);
+                timings.neighbor_search += step.neighbor_search;
+                timings.simulation += step.simulation;
+            }
+        }
+        self.profiler.record_cpu("neighbor search", timings.neighbor_search);
+        self.profiler.record_cpu("simulation", timings.simulation);
+    }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(CLEAR_COLOR),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
+    /// Fixed steps of `timestep` to run, a frame interval of the video while recording
+    fn advance_playback(&mut self, timestep: f32) -> u32 {
+        match &self.recorder {
+            Some(recorder) => self.playback.advance_by(recorder.frame_interval(), timestep),
+            None => self.playback.advance(Instant::now(), timestep),
+        }
+    }
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+    /// Advances the cache by whole frames, the playback accumulator interpolates between them
+    fn update_cache(&mut self) {
+        let Some(frame_interval) = self.cache.as_ref().map(|cache| cache.frame_interval) else { return };
+        let frames = self.advance_playback(frame_interval);
+        let Some(cache) = &mut self.cache else { return };
+        if !cache.advance(frames) {
+            self.playback.paused = true;
         }
 
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        let alpha = self.playback.interpolation_alpha(frame_interval);
+        self.show_cached_frame(if alpha < 1.0 { alpha } else { 0.0 });
+    }
+
+    fn show_cached_frame(&mut self, alpha: f32) {
+        let Some(cache) = &mut self.cache else { return };
+        let current = cache.current();
+        // While paused every update asks for the frame that's already shown
+        if alpha == 0.0 && self.shown_cache_frame == Some(current) {
+            return;
+        }
+        let load = Instant::now();
+
+        match cache.sample(alpha) {
+            Ok(frame) => {
+                self.show_frame(&frame);
+                self.shown_cache_frame = (alpha == 0.0).then_some(current);
+            }
+            Err(error) => {
+                log::error!("{error}");
+                self.playback.paused = true;
+            }
+        }
+        self.profiler.record_cpu("cache", load.elapsed());
+    }
 
+    /// Adds a pass to the render graph, it's ordered by the resources it reads and writes
+    pub fn add_render_node(&mut self, node: impl RenderNode + 'static) -> Result<(), RendererError> {
+        self.render_graph.add_node(node, &mut self.render_targets, &self.device)?;
         Ok(())
     }
-    
-    pub fn render_with_ui(&mut self, ui_renderer: &mut imgui_wgpu::Renderer, draw_data: &imgui::DrawData) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(CLEAR_COLOR),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
+    pub fn remove_render_node(&mut self, label: &str) -> Option<Box<dyn RenderNode>> {
+        self.render_graph.remove_node(label, &mut self.render_targets)
+    }
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.num_instances);
+    /// Renders a frame, `overlays` are drawn in order on top of it
+    pub fn render(&mut self, overlays: &mut [&mut dyn Overlay]) -> Result<(), wgpu::SurfaceError> {
+        self.profiler.begin_frame();
+        self.profiler.poll_gpu(&self.device);
 
-            ui_renderer.render(draw_data, &self.queue, &self.device, &mut render_pass).expect("Falied to render ui");
+        let output = match &self.surface {
+            Some(surface) => Some(surface.get_current_texture()?),
+            None => None,
+        };
+        let recording = self.recorder.is_some();
+        let render = Instant::now();
+        let (view, capture) = match &output {
+            Some(output) => {
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let copies = self.config.usage.contains(wgpu::TextureUsages::COPY_SRC);
+                (view, (recording && copies).then(|| self.take_capture()))
+            }
+            // Headless states draw into the capture texture
+            None => {
+                let capture = self.take_capture();
+                (capture.texture.create_view(&wgpu::TextureViewDescriptor::default()), Some(capture))
+            }
+        };
+        // While recording the rendered frame is copied out, windows without surface copies render it again below
+        let copy = capture.as_ref()
+            .filter(|_| recording)
+            .map(|capture| (output.as_ref().map_or(&capture.texture, |output| &output.texture), capture));
+        let copied = copy.is_some();
+
+        // Nodes get the whole state while recording, so the graph can't stay borrowed from it
+        let mut render_graph = std::mem::take(&mut self.render_graph);
+        let mut gpu_timer = self.profiler.gpu_timer.take();
+        let commands = render_graph.execute(self, &view, overlays, gpu_timer.as_mut(), copy);
+        self.render_graph = render_graph;
+
+        self.queue.submit(iter::once(commands));
+        if let Some(timer) = gpu_timer.as_mut() {
+            timer.map();
         }
+        self.profiler.gpu_timer = gpu_timer;
+        self.profiler.record_cpu("render", render.elapsed());
 
-        self.queue.submit(iter::once(encoder.finish()));
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
+        if capture.is_some() {
+            self.capture = capture;
+        }
+
+        if let Some(mut recorder) = self.recorder.take() {
+            let frame = match &self.capture {
+                Some(capture) if copied => capture.read(&self.device),
+                _ => self.capture_frame(),
+            };
+            let written = frame
+                .map_err(RecorderError::from)
+                .and_then(|frame| recorder.write_frame(&frame));
+            match written {
+                Ok(()) => self.recorder = Some(recorder),
+                Err(error) => log::error!("stopped recording: {error}"),
+            }
+        }
 
         Ok(())
     }
+
+    /// Renders the frame again into an offscreen texture and reads it back, overlays aren't drawn
+    pub fn capture_frame(&mut self) -> Result<CapturedFrame, wgpu::BufferAsyncError> {
+        let capture = self.take_capture();
+
+        let mut render_graph = std::mem::take(&mut self.render_graph);
+        let commands = render_graph.execute(self, &capture.view, &mut [], None, None);
+        self.render_graph = render_graph;
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+        capture.copy(&mut encoder);
+        self.queue.submit([commands, encoder.finish()]);
+
+        let frame = capture.read(&self.device);
+        self.capture = Some(capture);
+
+        frame
+    }
+
+    /// The capture texture for the current size and format, created on first use and after a resize
+    fn take_capture(&mut self) -> FrameCapture {
+        let size = (self.config.width, self.config.height);
+
+        match self.capture.take() {
+            Some(capture) if capture.size() == size && capture.format() == self.config.format => capture,
+            _ => FrameCapture::new(&self.device, self.config.format, size),
+        }
+    }
+
+    /// Records every following frame until [`State::stop_recording`]
+    pub fn start_recording(&mut self, recorder: Recorder) -> Result<(), RecorderError> {
+        self.stop_recording()?;
+        self.recorder = Some(recorder);
+
+        Ok(())
+    }
+
+    /// Finishes the video being recorded, if any
+    pub fn stop_recording(&mut self) -> Result<(), RecorderError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+
+/// `COPY_SRC` on backends whose surfaces can always be copied from, empty on the others
+///
+/// wgpu doesn't report the usages a surface supports and configuring one it
+/// doesn't is fatal, so this goes by what its dx12 backend always allows.
+fn surface_copy_usage(backend: wgpu::Backend) -> wgpu::TextureUsages {
+    match backend {
+        wgpu::Backend::Dx12 => wgpu::TextureUsages::COPY_SRC,
+        _ => wgpu::TextureUsages::empty(),
+    }
 }
@@ -0,0 +1,235 @@
+use winit::window::Window;
+use crate::{
+    State, Vertex, Instance, Camera,
+    PostProcessSettings, Tonemapper,
+    SolverParams, RenderSettings,
+    Shape, Quad, Geometry, Indices, RendererError, ParticleFrame,
+    CLEAR_COLOR, PARTICLE_SIZE,
+};
+
+
+/// Device and surface options used when creating a [`State`]
+#[derive(Debug, Clone)]
+pub struct StateSettings {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// Use a software adapter, useful when no gpu is available
+    pub force_fallback_adapter: bool,
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    /// `None` uses the first mode the surface supports
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// Pick an srgb surface format when one is available
+    pub prefer_srgb: bool,
+    /// Requested msaa sample count, lowered to what the adapter supports
+    pub sample_count: u32,
+    pub clear_color: wgpu::Color,
+    pub post_process: PostProcessSettings,
+    pub render: RenderSettings,
+    pub solver: SolverParams,
+    /// Time passes on the gpu when the adapter supports timestamp queries
+    pub gpu_timing: bool,
+}
+
+impl Default for StateSettings {
+    fn default() -> Self {
+        StateSettings {
+            backends: wgpu::Backends::PRIMARY,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            features: wgpu::Features::empty(),
+            // WebGL doesn't support all of wgpu's features, so if
+            // we're building for the web we'll have to disable some.
+            limits: wgpu::Limits::default(),
+            present_mode: None,
+            prefer_srgb: true,
+            sample_count: 1,
+            clear_color: CLEAR_COLOR,
+            post_process: PostProcessSettings::default(),
+            render: RenderSettings::default(),
+            solver: SolverParams::default(),
+            gpu_timing: true,
+        }
+    }
+}
+
+
+pub struct StateBuilder<'a> {
+    window: Option<Window>,
+    size: winit::dpi::PhysicalSize<u32>,
+    shader_source: wgpu::ShaderSource<'a>,
+    vertices: Vec<Vertex>,
+    indices: Indices,
+    instances: Vec<Instance>,
+    /// Restored into the solver once it exists
+    initial_frame: Option<ParticleFrame>,
+    camera: Camera,
+    settings: StateSettings,
+}
+
+impl<'a> StateBuilder<'a> {
+    /// Starts with a particle sized quad, no instances and the default camera
+    pub fn new(window: Window, shader_source: wgpu::ShaderSource<'a>) -> Self {
+        let size = window.inner_size();
+
+        StateBuilder {
+            window: Some(window),
+            ..Self::headless(shader_source, (size.width, size.height))
+        }
+    }
+
+    /// Renders frames of `size` offscreen, see [`State::capture_frame`]
+    pub fn headless(shader_source: wgpu::ShaderSource<'a>, size: (u32, u32)) -> Self {
+        StateBuilder {
+            window: None,
+            size: winit::dpi::PhysicalSize::new(size.0, size.1),
+            shader_source,
+            vertices: Quad.scale(PARTICLE_SIZE),
+            indices: Quad::INDICES.into(),
+            instances: Vec::new(),
+            initial_frame: None,
+            camera: Camera::default(),
+            settings: StateSettings::default(),
+        }
+    }
+
+    /// Particle geometry, indices can be `u16` or `u32`
+    pub fn geometry(mut self, vertices: &[Vertex], indices: impl Into<Indices>) -> Self {
+        self.vertices = vertices.to_vec();
+        self.indices = indices.into();
+        self
+    }
+
+    /// Particle geometry generated at runtime, e.g. `Geometry::icosphere(1).scale(PARTICLE_SIZE)`
+    pub fn shape(mut self, shape: Geometry) -> Self {
+        self.vertices = shape.vertices;
+        self.indices = shape.indices;
+        self
+    }
+
+    pub fn instances(mut self, instances: Vec<Instance>) -> Self {
+        self.instances = instances;
+        self.initial_frame = None;
+        self
+    }
+
+    /// Starts from a snapshot, e.g. `ParticleFrame::load("scene.ply")?`, including its solver attributes
+    pub fn initial_frame(mut self, frame: ParticleFrame) -> Self {
+        self.instances = frame.to_instances();
+        self.initial_frame = Some(frame);
+        self
+    }
+
+    pub fn camera(mut self, camera: Camera) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    pub fn settings(mut self, settings: StateSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.settings.backends = backends;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.settings.power_preference = power_preference;
+        self
+    }
+
+    pub fn force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.settings.force_fallback_adapter = force_fallback_adapter;
+        self
+    }
+
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.settings.features = features;
+        self
+    }
+
+    pub fn limits(mut self, limits: wgpu::Limits) -> Self {
+        self.settings.limits = limits;
+        self
+    }
+
+    /// Falls back to the first supported mode if the surface doesn't support `present_mode`
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.settings.present_mode = Some(present_mode);
+        self
+    }
+
+    pub fn vsync(self, vsync: bool) -> Self {
+        match vsync {
+            true => self.present_mode(wgpu::PresentMode::AutoVsync),
+            false => self.present_mode(wgpu::PresentMode::AutoNoVsync),
+        }
+    }
+
+    pub fn prefer_srgb(mut self, prefer_srgb: bool) -> Self {
+        self.settings.prefer_srgb = prefer_srgb;
+        self
+    }
+
+    /// 1 disables msaa, 2, 4 and 8 are used when the adapter supports them
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.settings.sample_count = sample_count;
+        self
+    }
+
+    pub fn clear_color(mut self, clear_color: wgpu::Color) -> Self {
+        self.settings.clear_color = clear_color;
+        self
+    }
+
+    pub fn exposure(mut self, exposure: f32) -> Self {
+        self.settings.post_process.exposure = exposure;
+        self
+    }
+
+    pub fn tonemapper(mut self, tonemapper: Tonemapper) -> Self {
+        self.settings.post_process.tonemapper = tonemapper;
+        self
+    }
+
+    pub fn bloom(mut self, bloom: bool) -> Self {
+        self.settings.post_process.bloom = bloom;
+        self
+    }
+
+    pub fn render_settings(mut self, render: RenderSettings) -> Self {
+        self.settings.render = render;
+        self
+    }
+
+    pub fn solver_params(mut self, solver: SolverParams) -> Self {
+        self.settings.solver = solver;
+        self
+    }
+
+    pub fn gpu_timing(mut self, gpu_timing: bool) -> Self {
+        self.settings.gpu_timing = gpu_timing;
+        self
+    }
+
+    pub async fn build(self) -> Result<State, RendererError> {
+        let mut state = State::from_settings(
+            self.window,
+            self.size,
+            self.shader_source,
+            &self.vertices,
+            &self.indices,
+            self.instances,
+            self.camera,
+            self.settings,
+        ).await?;
+
+        if let Some(frame) = &self.initial_frame {
+            frame.restore_solver(&mut state.solver);
+        }
+
+        Ok(state)
+    }
+}
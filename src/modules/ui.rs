@@ -0,0 +1,226 @@
+use std::borrow::Cow;
+use crate::{
+    State, ColorMap, RenderMode, Tonemapper, Profiler, StageTiming, Checkpoint, Recorder, VideoSettings,
+    PLAYBACK_SPEEDS, DEFAULT_CHECKPOINT_PATH, DEFAULT_VIDEO_PATH, DEFAULT_PNG_DIRECTORY,
+};
+
+
+/// Sliders for the solver, render and camera settings, changes are picked up by the next [`State::update`]
+pub fn parameter_panel(ui: &imgui::Ui, state: &mut State) {
+    ui.window("Parameters")
+        .size([320.0, 480.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            solver_parameters(ui, state);
+            render_parameters(ui, state);
+            camera_parameters(ui, state);
+        });
+}
+
+fn solver_parameters(ui: &imgui::Ui, state: &mut State) {
+    if !ui.collapsing_header("Solver", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+        return;
+    }
+
+    let params = &mut state.solver.params;
+    ui.slider_config("timestep", 0.0005, 0.01)
+        .display_format("%.4f")
+        .build(&mut params.timestep);
+    ui.slider("stiffness", 1.0, 200.0, &mut params.stiffness);
+    ui.slider("viscosity", 0.0, 1.0, &mut params.viscosity);
+
+    let mut gravity = params.gravity.to_array();
+    if ui.input_float3("gravity", &mut gravity).build() {
+        params.gravity = gravity.into();
+    }
+
+    if ui.button("reset velocities") {
+        let particle_count = state.instances.len();
+        state.solver.reset(particle_count);
+    }
+}
+
+fn render_parameters(ui: &imgui::Ui, state: &mut State) {
+    if !ui.collapsing_header("Rendering", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+        return;
+    }
+
+    let settings = &mut state.render_settings;
+    ui.slider("particle size", 0.1, 4.0, &mut settings.particle_scale);
+
+    let render_modes = [RenderMode::Soft, RenderMode::Flat, RenderMode::Square];
+    let mut render_mode = render_modes.iter().position(|mode| *mode == settings.render_mode).unwrap_or(0);
+    if ui.combo("render mode", &mut render_mode, &render_modes, |mode| Cow::Owned(format!("{mode:?}"))) {
+        settings.render_mode = render_modes[render_mode];
+    }
+
+    let mut color_map = ColorMap::ALL.iter().position(|map| *map == settings.color_map).unwrap_or(0);
+    if ui.combo("color map", &mut color_map, &ColorMap::ALL, |map| Cow::Borrowed(map.name())) {
+        settings.color_map = ColorMap::ALL[color_map];
+        settings.color_range = settings.color_map.default_range(&state.solver.params);
+    }
+    if settings.color_map != ColorMap::Instance {
+        ui.input_float2("color range", &mut settings.color_range).build();
+    }
+
+    let clear_color = state.clear_color;
+    let mut color = [clear_color.r, clear_color.g, clear_color.b].map(|channel| channel as f32);
+    if ui.color_edit3("clear color", &mut color) {
+        let [r, g, b] = color.map(f64::from);
+        state.clear_color = wgpu::Color { r, g, b, a: 1.0 };
+    }
+
+    let post_process = &mut state.post_process.settings;
+    ui.slider("exposure", 0.0, 4.0, &mut post_process.exposure);
+
+    let tonemappers = [Tonemapper::None, Tonemapper::Reinhard, Tonemapper::Aces];
+    let mut tonemapper = tonemappers.iter().position(|tonemapper| *tonemapper == post_process.tonemapper).unwrap_or(0);
+    if ui.combo("tonemapper", &mut tonemapper, &tonemappers, |tonemapper| Cow::Owned(format!("{tonemapper:?}"))) {
+        post_process.tonemapper = tonemappers[tonemapper];
+    }
+    ui.checkbox("bloom", &mut post_process.bloom);
+}
+
+fn camera_parameters(ui: &imgui::Ui, state: &mut State) {
+    if !ui.collapsing_header("Camera", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+        return;
+    }
+
+    let camera = &mut state.camera;
+    ui.slider("fov", 10.0, 120.0, &mut camera.fovy);
+    ui.slider_config("near", 0.001, 10.0)
+        .flags(imgui::SliderFlags::LOGARITHMIC)
+        .build(&mut camera.znear);
+    ui.slider_config("far", 1.0, 1000.0)
+        .flags(imgui::SliderFlags::LOGARITHMIC)
+        .build(&mut camera.zfar);
+
+    // The projection breaks down if the planes cross
+    camera.zfar = camera.zfar.max(camera.znear + 0.01);
+}
+
+
+/// Frame time graph with the cpu stage and gpu pass timings
+pub fn profiler_panel(ui: &imgui::Ui, profiler: &Profiler) {
+    ui.window("Profiler")
+        .size([320.0, 300.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            let frame_times = profiler.frame_times.iter().copied().collect::<Vec<_>>();
+            let average = profiler.average_frame_time();
+            let fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+
+            ui.plot_lines("##frame times", &frame_times)
+                .overlay_text(format!("{average:.2} ms ({fps:.0} fps)"))
+                .scale_min(0.0)
+                .scale_max(frame_times.iter().copied().fold(1.0, f32::max))
+                .graph_size([0.0, 60.0])
+                .build();
+
+            ui.separator();
+            ui.text("cpu");
+            stage_timings(ui, &profiler.cpu_stages);
+
+            ui.separator();
+            match profiler.gpu_timer {
+                Some(_) => {
+                    ui.text("gpu");
+                    stage_timings(ui, &profiler.gpu_passes);
+                }
+                None => ui.text_disabled("gpu timestamps unsupported, cpu timings only"),
+            }
+        });
+}
+
+fn stage_timings(ui: &imgui::Ui, stages: &[StageTiming]) {
+    for stage in stages {
+        ui.text(format!("{:<16} {:>7.3} ms  (avg {:.3})", stage.name, stage.last, stage.average));
+    }
+}
+
+
+/// Pause, step, rewind, speed and timestep controls, the transport is also available through [`State::input`]
+pub fn playback_panel(ui: &imgui::Ui, state: &mut State) {
+    ui.window("Playback")
+        .size([320.0, 200.0], imgui::Condition::FirstUseEver)
+        .build(|| {
+            if ui.button(if state.playback.paused { "play" } else { "pause" }) {
+                state.playback.toggle_pause();
+            }
+            ui.same_line();
+            if ui.button("step") {
+                state.playback.step();
+            }
+            ui.same_line();
+            if ui.button("rewind") {
+                state.rewind();
+            }
+            recording(ui, state);
+
+            let playback = &mut state.playback;
+            let mut speed = PLAYBACK_SPEEDS.iter().position(|speed| *speed == playback.speed).unwrap_or(3);
+            if ui.combo("speed", &mut speed, &PLAYBACK_SPEEDS, |speed| Cow::Owned(format!("{speed}x"))) {
+                playback.speed = PLAYBACK_SPEEDS[speed];
+            }
+            ui.checkbox("interpolate", &mut playback.interpolate);
+
+            if let Some(cache) = &mut state.cache {
+                let mut frame = cache.current();
+                if ui.slider("frame", 0, cache.len() - 1, &mut frame) {
+                    cache.seek(frame);
+                }
+                ui.checkbox("loop", &mut cache.looping);
+
+                ui.text(format!("time {:.3} s, frame {}/{}", state.solver.time, cache.current() + 1, cache.len()));
+                ui.text(format!("cache {}", cache.directory.display()));
+                if ui.button("simulate from here") {
+                    state.close_cache();
+                }
+                return;
+            }
+
+            ui.slider("substeps", 1, 16, &mut playback.substeps);
+            ui.slider("max steps per frame", 1, 32, &mut playback.max_steps_per_update);
+
+            ui.text(format!("time {:.3} s, step {}", state.solver.time, state.solver.steps));
+            ui.text(format!("history {}/{} steps", playback.history.len(), playback.history_capacity));
+
+            if ui.button("save checkpoint") {
+                if let Err(error) = Checkpoint::capture(state).save(DEFAULT_CHECKPOINT_PATH) {
+                    log::error!("failed to save {DEFAULT_CHECKPOINT_PATH}: {error}");
+                }
+            }
+            ui.same_line();
+            if ui.button("load checkpoint") {
+                match Checkpoint::load(DEFAULT_CHECKPOINT_PATH) {
+                    Ok(checkpoint) => checkpoint.restore(state),
+                    Err(error) => log::error!("failed to load {DEFAULT_CHECKPOINT_PATH}: {error}"),
+                }
+            }
+        });
+}
+
+fn recording(ui: &imgui::Ui, state: &mut State) {
+    if let Some(recorder) = &state.recorder {
+        ui.text(format!("recording, {} frames", recorder.frames()));
+        ui.same_line();
+        if ui.button("stop") {
+            if let Err(error) = state.stop_recording() {
+                log::error!("failed to finish the recording: {error}");
+            }
+        }
+        return;
+    }
+
+    let size = (state.config.width, state.config.height);
+    let mut recorder = None;
+    if ui.button("record video") {
+        recorder = Some(Recorder::video(&VideoSettings::new(DEFAULT_VIDEO_PATH), size));
+    }
+    ui.same_line();
+    if ui.button("record png") {
+        recorder = Some(Recorder::png_sequence(DEFAULT_PNG_DIRECTORY));
+    }
+
+    if let Some(Err(error)) = recorder.map(|recorder| state.start_recording(recorder?)) {
+        log::error!("failed to start recording: {error}");
+    }
+}
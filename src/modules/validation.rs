@@ -0,0 +1,190 @@
+use std::{fmt, path::Path};
+use crate::{
+    ProcessedShader, SourceLocation,
+    ShaderPreprocessor, PreprocessError,
+};
+
+
+#[derive(Debug)]
+pub enum ShaderValidationError {
+    Preprocess(PreprocessError),
+    Parse { location: Option<SourceLocation>, message: String },
+    Validation { location: Option<SourceLocation>, message: String },
+    MissingEntryPoint { entry_point: String },
+    VertexInput { entry_point: String, location: u32, message: String },
+}
+
+impl fmt::Display for ShaderValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Preprocess(error) => write!(f, "{error}"),
+            Self::Parse { location: Some(location), message } => write!(f, "{location}: parse error: {message}"),
+            Self::Parse { location: None, message } => write!(f, "parse error: {message}"),
+            Self::Validation { location: Some(location), message } => write!(f, "{location}: validation error: {message}"),
+            Self::Validation { location: None, message } => write!(f, "validation error: {message}"),
+            Self::MissingEntryPoint { entry_point } => write!(f, "no vertex entry point named `{entry_point}`"),
+            Self::VertexInput { entry_point, location, message } => write!(f, "`{entry_point}` @location({location}): {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ShaderValidationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Preprocess(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<PreprocessError> for ShaderValidationError {
+    fn from(error: PreprocessError) -> Self {
+        Self::Preprocess(error)
+    }
+}
+
+
+/// Parses and validates preprocessed wgsl with naga, without touching the gpu
+///
+/// Error locations are mapped back through the source map to the original files.
+pub fn validate_wgsl(shader: &ProcessedShader) -> Result<(naga::Module, naga::valid::ModuleInfo), ShaderValidationError> {
+    let module = naga::front::wgsl::parse_str(&shader.source).map_err(|error| {
+        let location = error.location(&shader.source)
+            .and_then(|location| shader.locate_offset(location.offset as usize))
+            .cloned();
+        let labels = error.labels()
+            .map(|(_, label)| label)
+            .filter(|label| !label.is_empty())
+            .collect::<Vec<_>>();
+
+        let message = match labels.is_empty() {
+            true => error.message().to_owned(),
+            false => format!("{} ({})", error.message(), labels.join(", ")),
+        };
+
+        ShaderValidationError::Parse { location, message }
+    })?;
+
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::empty())
+        .validate(&module)
+        .map_err(|error| {
+            let location = error.location(&shader.source)
+                .and_then(|location| shader.locate_offset(location.offset as usize))
+                .cloned();
+
+            // The inner error only says what failed, the source chain says why
+            let mut message = error.as_inner().to_string();
+            let mut source = std::error::Error::source(error.as_inner());
+            while let Some(error) = source {
+                message.push_str(&format!(": {error}"));
+                source = error.source();
+            }
+
+            ShaderValidationError::Validation { location, message }
+        })?;
+
+    Ok((module, info))
+}
+
+/// Preprocesses the file at `path` and validates the result
+pub fn validate_wgsl_file(path: impl AsRef<Path>, preprocessor: &ShaderPreprocessor) -> Result<(naga::Module, naga::valid::ModuleInfo), ShaderValidationError> {
+    let shader = preprocessor.process_file(path)?;
+    validate_wgsl(&shader)
+}
+
+
+/// Scalar kind and component count a vertex format is seen as from the shader
+fn vertex_format_shape(format: wgpu::VertexFormat) -> (naga::ScalarKind, u32) {
+    use wgpu::VertexFormat as F;
+    use naga::ScalarKind as K;
+
+    match format {
+        F::Uint8x2 | F::Uint16x2 | F::Uint32x2 => (K::Uint, 2),
+        F::Uint8x4 | F::Uint16x4 | F::Uint32x4 => (K::Uint, 4),
+        F::Uint32 => (K::Uint, 1),
+        F::Uint32x3 => (K::Uint, 3),
+        F::Sint8x2 | F::Sint16x2 | F::Sint32x2 => (K::Sint, 2),
+        F::Sint8x4 | F::Sint16x4 | F::Sint32x4 => (K::Sint, 4),
+        F::Sint32 => (K::Sint, 1),
+        F::Sint32x3 => (K::Sint, 3),
+        F::Float32 | F::Float64 => (K::Float, 1),
+        F::Unorm8x2 | F::Snorm8x2 | F::Unorm16x2 | F::Snorm16x2
+            | F::Float16x2 | F::Float32x2 | F::Float64x2 => (K::Float, 2),
+        F::Float32x3 | F::Float64x3 => (K::Float, 3),
+        F::Unorm8x4 | F::Snorm8x4 | F::Unorm16x4 | F::Snorm16x4
+            | F::Float16x4 | F::Float32x4 | F::Float64x4 => (K::Float, 4),
+    }
+}
+
+fn type_shape(module: &naga::Module, ty: naga::Handle<naga::Type>) -> Option<(naga::ScalarKind, u32)> {
+    match module.types[ty].inner {
+        naga::TypeInner::Scalar { kind, .. } => Some((kind, 1)),
+        naga::TypeInner::Vector { size, kind, .. } => Some((kind, size as u32)),
+        _ => None,
+    }
+}
+
+/// Collects `(location, type)` of every vertex input, flattening struct arguments
+fn vertex_inputs(module: &naga::Module, function: &naga::Function) -> Vec<(u32, naga::Handle<naga::Type>)> {
+    let mut inputs = Vec::new();
+
+    for argument in function.arguments.iter() {
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(naga::Binding::Location { location, .. }), _) => inputs.push((*location, argument.ty)),
+            (None, naga::TypeInner::Struct { members, .. }) => {
+                inputs.extend(members.iter().filter_map(|member| match member.binding {
+                    Some(naga::Binding::Location { location, .. }) => Some((location, member.ty)),
+                    _ => None,
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    inputs
+}
+
+/// Checks that every `@location` input of the vertex entry point is provided by
+/// `buffers` with a matching type, and that every attribute fits in its stride
+pub fn check_vertex_inputs(module: &naga::Module, entry_point: &str, buffers: &[wgpu::VertexBufferLayout]) -> Result<(), ShaderValidationError> {
+    let entry = module.entry_points.iter()
+        .find(|entry| entry.stage == naga::ShaderStage::Vertex && entry.name == entry_point)
+        .ok_or_else(|| ShaderValidationError::MissingEntryPoint { entry_point: entry_point.to_owned() })?;
+
+    let error = |location: u32, message: String| ShaderValidationError::VertexInput {
+        entry_point: entry_point.to_owned(),
+        location,
+        message,
+    };
+
+    for buffer in buffers {
+        for attribute in buffer.attributes {
+            if attribute.offset + attribute.format.size() > buffer.array_stride {
+                return Err(error(attribute.shader_location, format!(
+                    "attribute at offset {} with format {:?} overruns array stride {}",
+                    attribute.offset, attribute.format, buffer.array_stride
+                )));
+            }
+        }
+    }
+
+    for (location, ty) in vertex_inputs(module, &entry.function) {
+        let attribute = buffers.iter()
+            .flat_map(|buffer| buffer.attributes.iter())
+            .find(|attribute| attribute.shader_location == location)
+            .ok_or_else(|| error(location, "not provided by any vertex buffer".to_owned()))?;
+
+        let expected = type_shape(module, ty)
+            .ok_or_else(|| error(location, "input is not a scalar or vector".to_owned()))?;
+        let provided = vertex_format_shape(attribute.format);
+
+        if expected != provided {
+            return Err(error(location, format!(
+                "shader expects {} x {:?} but the buffer provides {:?}",
+                expected.1, expected.0, attribute.format
+            )));
+        }
+    }
+
+    Ok(())
+}
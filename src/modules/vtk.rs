@@ -0,0 +1,230 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use crate::{ParticleFrame, AttributeValues, attribute_token};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VtkFormat {
+    /// `.vtk` legacy polydata
+    Legacy,
+    /// `.vtu` xml unstructured grid
+    Xml,
+}
+
+impl VtkFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VtkFormat::Legacy => "vtk",
+            VtkFormat::Xml => "vtu",
+        }
+    }
+}
+
+
+/// Escapes the characters that would end an xml attribute or start markup
+fn xml_escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    Cow::Owned(escaped)
+}
+
+fn write_values(writer: &mut impl Write, values: &AttributeValues) -> io::Result<()> {
+    match values {
+        AttributeValues::Scalar(values) => {
+            for value in values {
+                writeln!(writer, "{value}")?;
+            }
+        }
+        AttributeValues::Vector(values) => {
+            for value in values {
+                writeln!(writer, "{} {} {}", value.x, value.y, value.z)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ascii legacy vtk, every particle is a vertex cell so ParaView shows it without a filter
+///
+/// Whitespace in attribute names is replaced by underscores.
+pub fn write_vtk(writer: impl Write, frame: &ParticleFrame) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let count = frame.len();
+
+    writeln!(writer, "# vtk DataFile Version 3.0")?;
+    writeln!(writer, "fluid-renderer particles")?;
+    writeln!(writer, "ASCII")?;
+    writeln!(writer, "DATASET POLYDATA")?;
+    writeln!(writer, "FIELD FieldData 1")?;
+    writeln!(writer, "TIME 1 1 float")?;
+    writeln!(writer, "{}", frame.time)?;
+
+    writeln!(writer, "POINTS {count} float")?;
+    for position in &frame.positions {
+        writeln!(writer, "{} {} {}", position.x, position.y, position.z)?;
+    }
+
+    writeln!(writer, "VERTICES {count} {}", count * 2)?;
+    for index in 0..count {
+        writeln!(writer, "1 {index}")?;
+    }
+
+    if !frame.attributes.is_empty() {
+        writeln!(writer, "POINT_DATA {count}")?;
+    }
+    for attribute in &frame.attributes {
+        match attribute.values {
+            AttributeValues::Scalar(_) => writeln!(writer, "SCALARS {} float 1\nLOOKUP_TABLE default", attribute_token(&attribute.name))?,
+            AttributeValues::Vector(_) => writeln!(writer, "VECTORS {} float", attribute_token(&attribute.name))?,
+        }
+        write_values(&mut writer, &attribute.values)?;
+    }
+
+    writer.flush()
+}
+
+/// Ascii xml unstructured grid with a vertex cell per particle
+pub fn write_vtu(writer: impl Write, frame: &ParticleFrame) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let count = frame.len();
+
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(writer, r#"<VTKFile type="UnstructuredGrid" version="0.1" byte_order="LittleEndian">"#)?;
+    writeln!(writer, r#"  <UnstructuredGrid>"#)?;
+    writeln!(writer, r#"    <FieldData>"#)?;
+    writeln!(writer, r#"      <DataArray type="Float32" Name="TimeValue" NumberOfTuples="1" format="ascii">{}</DataArray>"#, frame.time)?;
+    writeln!(writer, r#"    </FieldData>"#)?;
+    writeln!(writer, r#"    <Piece NumberOfPoints="{count}" NumberOfCells="{count}">"#)?;
+
+    writeln!(writer, r#"      <Points>"#)?;
+    writeln!(writer, r#"        <DataArray type="Float32" NumberOfComponents="3" format="ascii">"#)?;
+    for position in &frame.positions {
+        writeln!(writer, "{} {} {}", position.x, position.y, position.z)?;
+    }
+    writeln!(writer, r#"        </DataArray>"#)?;
+    writeln!(writer, r#"      </Points>"#)?;
+
+    writeln!(writer, r#"      <Cells>"#)?;
+    let cell_arrays = [("Int64", "connectivity", 0), ("Int64", "offsets", 1)];
+    for (data_type, name, offset) in cell_arrays {
+        writeln!(writer, r#"        <DataArray type="{data_type}" Name="{name}" format="ascii">"#)?;
+        for index in 0..count {
+            writeln!(writer, "{}", index + offset)?;
+        }
+        writeln!(writer, r#"        </DataArray>"#)?;
+    }
+    // 1 is VTK_VERTEX
+    writeln!(writer, r#"        <DataArray type="UInt8" Name="types" format="ascii">"#)?;
+    for _ in 0..count {
+        writeln!(writer, "1")?;
+    }
+    writeln!(writer, r#"        </DataArray>"#)?;
+    writeln!(writer, r#"      </Cells>"#)?;
+
+    writeln!(writer, r#"      <PointData>"#)?;
+    for attribute in &frame.attributes {
+        writeln!(
+            writer,
+            r#"        <DataArray type="Float32" Name="{}" NumberOfComponents="{}" format="ascii">"#,
+            xml_escape(&attribute.name),
+            attribute.values.components(),
+        )?;
+        write_values(&mut writer, &attribute.values)?;
+        writeln!(writer, r#"        </DataArray>"#)?;
+    }
+    writeln!(writer, r#"      </PointData>"#)?;
+
+    writeln!(writer, r#"    </Piece>"#)?;
+    writeln!(writer, r#"  </UnstructuredGrid>"#)?;
+    writeln!(writer, r#"</VTKFile>"#)?;
+
+    writer.flush()
+}
+
+/// Time collection referencing one file per frame, `files` are `(time, path relative to the pvd)`
+pub fn write_pvd(writer: impl Write, files: &[(f32, String)]) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(writer, r#"<VTKFile type="Collection" version="0.1">"#)?;
+    writeln!(writer, r#"  <Collection>"#)?;
+    for (time, file) in files {
+        writeln!(writer, r#"    <DataSet timestep="{time}" part="0" file="{}"/>"#, xml_escape(file))?;
+    }
+    writeln!(writer, r#"  </Collection>"#)?;
+    writeln!(writer, r#"</VTKFile>"#)?;
+
+    writer.flush()
+}
+
+/// Writes `frame` to `path` in `format`
+pub fn save_vtk(path: impl AsRef<Path>, frame: &ParticleFrame, format: VtkFormat) -> io::Result<()> {
+    let file = File::create(path)?;
+
+    match format {
+        VtkFormat::Legacy => write_vtk(file, frame),
+        VtkFormat::Xml => write_vtu(file, frame),
+    }
+}
+
+
+/// Numbered frame files in a directory plus a `.pvd` collection that opens them as one time series
+///
+/// The collection is rewritten after every frame, so an interrupted run still opens in ParaView.
+#[derive(Debug, Clone)]
+pub struct VtkSeries {
+    pub directory: PathBuf,
+    pub name: String,
+    pub format: VtkFormat,
+    /// Time and file name of every frame written so far
+    pub frames: Vec<(f32, String)>,
+}
+
+impl VtkSeries {
+    pub fn new(directory: impl Into<PathBuf>, name: &str, format: VtkFormat) -> io::Result<Self> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+
+        Ok(VtkSeries {
+            directory,
+            name: name.to_owned(),
+            format,
+            frames: Vec::new(),
+        })
+    }
+
+    pub fn pvd_path(&self) -> PathBuf {
+        self.directory.join(format!("{}.pvd", self.name))
+    }
+
+    /// Writes the next frame and updates the collection, returns the frame's path
+    pub fn write_frame(&mut self, frame: &ParticleFrame) -> io::Result<PathBuf> {
+        let file_name = format!("{}_{:05}.{}", self.name, self.frames.len(), self.format.extension());
+        let path = self.directory.join(&file_name);
+
+        save_vtk(&path, frame, self.format)?;
+        self.frames.push((frame.time, file_name));
+        write_pvd(File::create(self.pvd_path())?, &self.frames)?;
+
+        Ok(path)
+    }
+}
@@ -1,10 +1,17 @@
 // Vertex shader
 
-struct CameraUniform {
-    view_projection: mat4x4<f32>,
-};
-@group(0) @binding(0)
-var<uniform> camera: CameraUniform;
+#include "shaders/camera.wgsl"
+
+#define RENDER_MODE_SOFT 0u
+#define RENDER_MODE_FLAT 1u
+#define RENDER_MODE_SQUARE 2u
+
+struct RenderUniform {
+    particle_scale: f32,
+    render_mode: u32,
+}
+@group(1) @binding(0)
+var<uniform> render_settings: RenderUniform;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -30,7 +37,7 @@ fn vs_main(
     var out: VertexOutput;
 
     out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_projection * vec4(model.position + instance.position, 1.0); 
+    out.clip_position = camera.view_projection * vec4(model.position * render_settings.particle_scale + instance.position, 1.0); 
     out.color = instance.color;
 
     return out;
@@ -43,10 +50,18 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var position = in.tex_coords - vec2(0.5, 0.5);
     var distance_from_middle = length(position);
     var alpha = 1f - step(0.4, distance_from_middle);
+    if render_settings.render_mode == RENDER_MODE_SQUARE {
+        alpha = 1f;
+    }
 
+#ifdef INSTANCE_COLOR
+    var color = in.color;
+#else
     var color = vec3(0.1f, 0.1f, 1.0f); 
-    /* color = in.color; */
-    color *= smoothstep(0.5, 0.0, distance_from_middle);
+#endif
+    if render_settings.render_mode == RENDER_MODE_SOFT {
+        color *= smoothstep(0.5, 0.0, distance_from_middle);
+    }
 
     return vec4(color, alpha);
 }
@@ -0,0 +1,5 @@
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
@@ -0,0 +1,71 @@
+// Static scene meshes with lambert or blinn-phong shading
+
+#include "camera.wgsl"
+
+#define SHADING_LAMBERT 0u
+#define SHADING_PHONG 1u
+
+struct LightingUniform {
+    // Towards the light
+    direction: vec4<f32>,
+    // Rgb and ambient factor in w
+    color: vec4<f32>,
+    eye: vec4<f32>,
+}
+@group(0) @binding(1)
+var<uniform> lighting: LightingUniform;
+
+struct MeshUniform {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    color: vec4<f32>,
+    specular: f32,
+    shininess: f32,
+    shading: u32,
+}
+@group(1) @binding(0)
+var<uniform> mesh: MeshUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+@vertex
+fn vs_mesh(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_position = mesh.model * vec4(vertex.position, 1.0);
+    out.world_position = world_position.xyz;
+    out.normal = (mesh.normal * vec4(vertex.normal, 0.0)).xyz;
+    out.clip_position = camera.view_projection * world_position;
+
+    return out;
+}
+
+@fragment
+fn fs_mesh(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    // Open meshes like a cup show their inside, so back faces are lit too
+    var normal = normalize(in.normal);
+    if !front_facing {
+        normal = -normal;
+    }
+
+    let light = normalize(lighting.direction.xyz);
+    let diffuse = max(dot(normal, light), 0.0);
+    var color = mesh.color.rgb * lighting.color.rgb * (lighting.color.w + diffuse);
+
+    if mesh.shading == SHADING_PHONG && diffuse > 0.0 {
+        let view = normalize(lighting.eye.xyz - in.world_position);
+        let halfway = normalize(light + view);
+        color += lighting.color.rgb * mesh.specular * pow(max(dot(normal, halfway), 0.0), mesh.shininess);
+    }
+
+    return vec4(color, 1.0);
+}
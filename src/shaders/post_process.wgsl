@@ -0,0 +1,97 @@
+#include "fullscreen.wgsl"
+
+// Must match `Tonemapper` on the rust side
+#define TONEMAP_NONE 0u
+#define TONEMAP_REINHARD 1u
+#define TONEMAP_ACES 2u
+
+struct PostProcessUniform {
+    exposure: f32,
+    tonemapper: u32,
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var bloom_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var linear_sampler: sampler;
+@group(0) @binding(3)
+var<uniform> settings: PostProcessUniform;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + luminance(color));
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+fn tonemap_aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3(0.0), vec3(1.0));
+}
+
+// Bloom
+
+@fragment
+fn fs_bright(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(source_texture, linear_sampler, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+
+    // Soft knee, highlights fade in around the threshold instead of popping
+    let knee = settings.bloom_threshold * 0.5 + 0.0001;
+    var soft = clamp(brightness - settings.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - settings.bloom_threshold) / max(brightness, 0.0001);
+
+    return vec4(color * contribution, 1.0);
+}
+
+// 9 tap gaussian folded into 5 bilinear samples
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let texel = direction / vec2<f32>(textureDimensions(source_texture));
+
+    var color = textureSample(source_texture, linear_sampler, uv).rgb * 0.2270270270;
+    color += textureSample(source_texture, linear_sampler, uv + texel * 1.3846153846).rgb * 0.3162162162;
+    color += textureSample(source_texture, linear_sampler, uv - texel * 1.3846153846).rgb * 0.3162162162;
+    color += textureSample(source_texture, linear_sampler, uv + texel * 3.2307692308).rgb * 0.0702702703;
+    color += textureSample(source_texture, linear_sampler, uv - texel * 3.2307692308).rgb * 0.0702702703;
+
+    return vec4(color, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2(0.0, 1.0));
+}
+
+// Tonemapping
+
+@fragment
+fn fs_tonemap(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(source_texture, linear_sampler, in.uv).rgb;
+    color += textureSample(bloom_texture, linear_sampler, in.uv).rgb * settings.bloom_intensity;
+    color *= settings.exposure;
+
+    if settings.tonemapper == TONEMAP_REINHARD {
+        color = tonemap_reinhard(color);
+    } else if settings.tonemapper == TONEMAP_ACES {
+        color = tonemap_aces(color);
+    }
+
+    return vec4(color, 1.0);
+}
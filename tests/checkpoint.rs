@@ -0,0 +1,57 @@
+use glam::vec3a;
+use fluid_renderer::{
+    create_cube, scene_rng, Camera, Checkpoint, CheckpointError, ParticleSnapshot, Solver,
+    SolverParams, CHECKPOINT_VERSION, DEFAULT_SEED,
+};
+
+
+#[test]
+fn restored_checkpoint_continues_bit_for_bit() {
+    let params = SolverParams { viscosity: 0.02, ..Default::default() };
+    let mut instances = create_cube(&mut scene_rng(DEFAULT_SEED), 0.1, (6, 6, 6), None, (-0.3, -0.3, -0.3));
+    let mut solver = Solver::new(params, instances.len());
+    for _ in 0..10 {
+        solver.step(&mut instances);
+    }
+
+    let checkpoint = Checkpoint {
+        particles: ParticleSnapshot::capture(&instances, &solver),
+        params: solver.params,
+        substeps: 3,
+        camera: Camera { eye: vec3a(-4.0, 2.0, 2.0), ..Default::default() },
+    };
+    let mut bytes = Vec::new();
+    checkpoint.write(&mut bytes).unwrap();
+    let read = Checkpoint::read(bytes.as_slice()).unwrap();
+    assert_eq!(read.substeps, 3);
+    assert_eq!(read.camera.eye, checkpoint.camera.eye);
+
+    let mut restored_instances = Vec::new();
+    let mut restored_solver = Solver::new(read.params, 0);
+    read.particles.restore(&mut restored_instances, &mut restored_solver);
+
+    solver.step(&mut instances);
+    restored_solver.step(&mut restored_instances);
+
+    assert_eq!(restored_solver.steps, solver.steps);
+    assert_eq!(restored_solver.time.to_bits(), solver.time.to_bits());
+    for (restored, original) in restored_instances.iter().zip(&instances) {
+        assert_eq!(restored.position.to_array().map(f32::to_bits), original.position.to_array().map(f32::to_bits));
+    }
+    for (restored, original) in restored_solver.velocities.iter().zip(&solver.velocities) {
+        assert_eq!(restored.to_array().map(f32::to_bits), original.to_array().map(f32::to_bits));
+    }
+    assert_eq!(restored_instances.len(), instances.len());
+    assert_eq!(restored_solver.velocities.len(), solver.velocities.len());
+}
+
+#[test]
+fn foreign_files_are_rejected() {
+    assert!(matches!(Checkpoint::read(&b"PLY\n0000"[..]), Err(CheckpointError::InvalidMagic)));
+
+    let mut bytes = b"FRCP".to_vec();
+    bytes.extend_from_slice(&(CHECKPOINT_VERSION + 1).to_le_bytes());
+    let error = Checkpoint::read(bytes.as_slice()).unwrap_err();
+    assert!(matches!(error, CheckpointError::UnsupportedVersion(version) if version == CHECKPOINT_VERSION + 1));
+    assert!(error.to_string().contains("isn't supported"), "{error}");
+}
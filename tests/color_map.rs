@@ -0,0 +1,31 @@
+use fluid_renderer::{ColorMap, RenderSettings, SolverParams};
+
+
+#[test]
+fn empty_color_range_splits_at_its_value() {
+    let settings = RenderSettings { color_range: [1.0, 1.0], ..Default::default() };
+
+    assert_eq!(settings.color_position(0.5), 0.0);
+    assert_eq!(settings.color_position(1.0), 1.0);
+    assert_eq!(settings.color_position(2.0), 1.0);
+}
+
+#[test]
+fn color_range_maps_to_the_gradient() {
+    let settings = RenderSettings { color_range: [2.0, 4.0], ..Default::default() };
+
+    assert_eq!(settings.color_position(2.0), 0.0);
+    assert_eq!(settings.color_position(3.0), 0.5);
+    assert_eq!(settings.color_position(4.0), 1.0);
+}
+
+#[test]
+fn density_range_covers_the_rest_density() {
+    let params = SolverParams::default();
+    let settings = RenderSettings {
+        color_range: ColorMap::Density.default_range(&params),
+        ..Default::default()
+    };
+
+    assert_eq!(settings.color_position(params.rest_density), 0.5);
+}
@@ -0,0 +1,96 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+use fluid_renderer::{wgpu, RendererError, State, StateBuilder};
+
+
+/// Set to run the tests without a gpu, tests that need one then pass without checking anything
+pub const SKIP_GPU_TESTS: &str = "FLUID_RENDERER_SKIP_GPU_TESTS";
+
+/// Software adapters are often only exposed through gl, e.g. llvmpipe
+const BACKENDS: wgpu::Backends = wgpu::Backends::all();
+
+pub fn gpu_tests_skipped() -> bool {
+    std::env::var_os(SKIP_GPU_TESTS).is_some()
+}
+
+/// Panic message when no adapter was found and the gpu tests weren't skipped explicitly
+pub fn no_adapter() -> String {
+    format!("no software adapter available, install one (e.g. mesa's llvmpipe) or set {SKIP_GPU_TESTS}=1")
+}
+
+/// Device on a software adapter, `None` when the gpu tests are skipped
+pub fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    if gpu_tests_skipped() {
+        return None;
+    }
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: BACKENDS,
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        force_fallback_adapter: true,
+        compatible_surface: None,
+    }))
+    .unwrap_or_else(|| panic!("{}", no_adapter()));
+
+    let device = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap();
+    Some(device)
+}
+
+/// Builds a headless state on a software adapter, `None` when the gpu tests are skipped
+pub fn headless_state(builder: StateBuilder) -> Option<State> {
+    if gpu_tests_skipped() {
+        return None;
+    }
+
+    let builder = builder
+        .backends(BACKENDS)
+        .force_fallback_adapter(true)
+        .gpu_timing(false);
+
+    match pollster::block_on(builder.build()) {
+        Ok(state) => Some(state),
+        Err(RendererError::NoAdapter) => panic!("{}", no_adapter()),
+        Err(error) => panic!("{error}"),
+    }
+}
+
+
+/// Fresh directory for one test, removed when it's dropped, also when the test panics
+pub struct TempDir(PathBuf);
+
+/// Unique per test binary run, so tests can run in parallel
+pub fn temp_dir(name: &str) -> TempDir {
+    let path = std::env::temp_dir().join(format!("fluid-renderer-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+
+    TempDir(path)
+}
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,199 @@
+//! Renders canonical scenes headless on a software adapter and compares them to `tests/golden/*.png`
+//!
+//! Run with `UPDATE_GOLDEN=1` to accept changed output as the new references. On a
+//! mismatch the rendered frame and a diff image are written next to the test binary's
+//! temporary directory, the failure message has their paths.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use glam::{vec3, vec3a, Mat4, Quat, Vec3};
+use fluid_renderer::{
+    create_cube, create_square, scene_rng, compare_images, shader_preprocessor,
+    State, StateBuilder, Shader, ShaderPreprocessor, Camera, Instance, CapturedFrame, ColorMap, Recorder,
+    Geometry, MeshMaterial, MeshNode,
+    CUBE_DIMENSIONS, DEFAULT_SEED, GRID_DIMENSIONS,
+};
+
+
+const SIZE: (u32, u32) = (256, 256);
+/// Largest [`fluid_renderer::color_delta`] of a pixel that still counts as unchanged
+const TOLERANCE: f32 = 0.1;
+/// Share of the pixels allowed to differ, covers edge antialiasing on other adapters
+const MAX_DIFFERING: f32 = 0.002;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// `None` when the gpu tests are skipped, see [`common::SKIP_GPU_TESTS`]
+///
+/// Msaa stays off, llvmpipe's gl backend renders the whole frame black with it.
+fn render(preprocessor: ShaderPreprocessor, configure: impl FnOnce(StateBuilder) -> StateBuilder, setup: impl FnOnce(&mut State)) -> Option<CapturedFrame> {
+    let shader = Shader::with_preprocessor(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl"), preprocessor).unwrap();
+    let mut state = common::headless_state(configure(State::headless_builder(shader.source(), SIZE)))?;
+
+    // Paused, so the first update uploads the scene without stepping the solver
+    state.playback.paused = true;
+    setup(&mut state);
+    state.update();
+
+    Some(state.capture_frame().expect("failed to read the frame back"))
+}
+
+fn assert_matches_golden(name: &str, frame: Option<CapturedFrame>) {
+    let Some(frame) = frame else { return };
+    let reference = golden_dir().join(format!("{name}.png"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        frame.save_png(&reference).unwrap();
+        return;
+    }
+
+    let expected = CapturedFrame::load_png(&reference)
+        .unwrap_or_else(|error| panic!("{}: {error}, run with UPDATE_GOLDEN=1 to create it", reference.display()));
+    let diff = compare_images(&expected, &frame, TOLERANCE);
+
+    if diff.as_ref().is_some_and(|diff| diff.differing_fraction() <= MAX_DIFFERING) {
+        return;
+    }
+
+    let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    std::fs::create_dir_all(&output_dir).unwrap();
+    let actual = output_dir.join(format!("{name}.actual.png"));
+    frame.save_png(&actual).unwrap();
+
+    match diff {
+        Some(diff) => {
+            let diff_path = output_dir.join(format!("{name}.diff.png"));
+            diff.image.save_png(&diff_path).unwrap();
+            panic!(
+                "{name}: {} of {} pixels differ (max delta {:.3}), see {} and {}",
+                diff.differing_pixels, diff.total_pixels, diff.max_delta, actual.display(), diff_path.display(),
+            );
+        }
+        None => panic!(
+            "{name}: rendered {}x{} but the reference is {}x{}, see {}",
+            frame.width, frame.height, expected.width, expected.height, actual.display(),
+        ),
+    }
+}
+
+fn camera(eye: Vec3, fovy: f32) -> Camera {
+    Camera {
+        aspect: SIZE.0 as f32 / SIZE.1 as f32,
+        eye: eye.into(),
+        fovy,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn default_cube() {
+    // The particles `run` starts with, without its floor
+    let instances = create_cube(&mut scene_rng(DEFAULT_SEED), 0.1, CUBE_DIMENSIONS, None, (-1.0, -1.0, -2.0));
+    let frame = render(
+        shader_preprocessor(),
+        |builder| builder.instances(instances).camera(camera(vec3(-4.0, 2.0, 2.0), 45.0)),
+        |_| {},
+    );
+
+    assert_matches_golden("default_cube", frame);
+}
+
+#[test]
+fn single_particle() {
+    let instance = Instance { position: Vec3::ZERO, color: Vec3::ONE };
+    let frame = render(
+        shader_preprocessor(),
+        |builder| builder.instances(vec![instance]).camera(camera(vec3(0.0, 0.0, 0.5), 45.0)),
+        |_| {},
+    );
+
+    assert_matches_golden("single_particle", frame);
+}
+
+#[test]
+fn color_mapped_grid() {
+    let instances = create_square(GRID_DIMENSIONS, (2, 2), (0.0, 0.0, 0.0));
+    let frame = render(
+        shader_preprocessor(),
+        |builder| builder.instances(instances).camera(Camera { eye: vec3a(0.0, 0.0, 2.5), ..camera(Vec3::ZERO, 45.0) }),
+        |state| {
+            // Speeds rising along the grid sweep the whole color range
+            let count = state.instances.len();
+            for (index, velocity) in state.solver.velocities.iter_mut().enumerate() {
+                *velocity = Vec3::X * 2.0 * index as f32 / (count - 1) as f32;
+            }
+            state.render_settings.color_map = ColorMap::Speed;
+        },
+    );
+
+    assert_matches_golden("color_mapped_grid", frame);
+}
+
+/// Flattened cube under the particles, like the floor `run` adds
+fn add_floor(state: &mut State) {
+    let mut meshes = MeshNode::new(state).unwrap();
+    meshes.add_mesh(
+        &state.device,
+        &Geometry::cube().to_triangle_mesh(),
+        Mat4::from_scale_rotation_translation(vec3(2.0, 0.1, 2.0), Quat::IDENTITY, vec3(0.0, -0.5, 0.0)),
+        MeshMaterial::default(),
+    );
+    state.add_render_node(meshes).unwrap();
+}
+
+#[test]
+fn particles_on_a_floor() {
+    let instances = create_square((8, 8), (2, 2), (0.0, 0.0, 0.0));
+    let frame = render(
+        shader_preprocessor(),
+        |builder| builder.instances(instances).camera(camera(vec3(0.0, 1.5, 2.5), 45.0)),
+        add_floor,
+    );
+
+    assert_matches_golden("particles_on_a_floor", frame);
+}
+
+/// The multisampled passes and their resolve into the hdr target, as `run` renders them
+///
+/// Only checked for validation errors, which panic. There's no reference image since
+/// llvmpipe resolves to black, see [`render`].
+#[test]
+fn msaa_passes_validate() {
+    let instances = create_square((8, 8), (2, 2), (0.0, 0.0, 0.0));
+    let mut sample_count = 0;
+    let frame = render(
+        shader_preprocessor(),
+        |builder| builder.instances(instances).camera(camera(vec3(0.0, 1.5, 2.5), 45.0)).sample_count(4),
+        |state| {
+            sample_count = state.sample_count;
+            add_floor(state);
+        },
+    );
+    let Some(frame) = frame else { return };
+
+    assert_eq!(sample_count, 4);
+    assert_eq!((frame.width, frame.height), SIZE);
+}
+
+/// Recording copies the frame `render` drew instead of drawing it again, it has to match a capture
+#[test]
+fn recorded_frames_match_captures() {
+    let shader = Shader::with_preprocessor(concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl"), shader_preprocessor()).unwrap();
+    let instances = create_square((8, 8), (2, 2), (0.0, 0.0, 0.0));
+    let builder = State::headless_builder(shader.source(), SIZE).instances(instances).camera(camera(vec3(0.0, 1.5, 2.5), 45.0));
+    let Some(mut state) = common::headless_state(builder) else { return };
+    state.playback.paused = true;
+    state.update();
+
+    let dir = common::temp_dir("recording");
+    state.start_recording(Recorder::png_sequence(dir.path()).unwrap()).unwrap();
+    state.render(&mut []).unwrap();
+    state.stop_recording().unwrap();
+
+    let recorded = CapturedFrame::load_png(dir.join("frame_00000.png")).unwrap();
+    assert_eq!(recorded, state.capture_frame().unwrap());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use glam::vec3;
+use fluid_renderer::{read_geo, write_geo, AttributeValues, GeoCache, ParticleFileError, ParticleFrame};
+
+
+fn frame() -> ParticleFrame {
+    let positions = vec![vec3(0.1, -2.5, 3.0), vec3(1e-7, 12345.678, -0.333)];
+    ParticleFrame { positions, time: 2.5, attributes: Vec::new() }
+        .with_attribute(ParticleFrame::VELOCITY, AttributeValues::Vector(vec![vec3(-1.0, 0.0, 9.81), vec3(0.1, 0.2, 0.3)]))
+        .with_attribute(ParticleFrame::COLOR, AttributeValues::Vector(vec![vec3(1.0, 0.5, 0.0), vec3(0.2, 0.4, 0.6)]))
+        .with_attribute(ParticleFrame::DENSITY, AttributeValues::Scalar(vec![1000.0, 999.123]))
+}
+
+fn write(frame: &ParticleFrame) -> String {
+    let mut data = Vec::new();
+    write_geo(&mut data, frame).unwrap();
+    String::from_utf8(data).unwrap()
+}
+
+#[test]
+fn geo_round_trips_velocity_color_id_and_time() {
+    // Births and deaths leave gaps in the ids
+    let frame = frame().with_attribute("id", AttributeValues::Scalar(vec![4.0, 17.0]));
+    let geo = write(&frame);
+
+    for definition in ["v 3 vector 0 0 0", "Cd 3 float 0 0 0", "density 1 float 0", "id 1 int 0", "time 1 float 0"] {
+        assert!(geo.lines().any(|line| line == definition), "`{definition}` missing from\n{geo}");
+    }
+    assert_eq!(read_geo(geo.as_bytes()).unwrap(), frame);
+}
+
+#[test]
+fn particles_without_ids_are_numbered() {
+    let read = read_geo(write(&frame()).as_bytes()).unwrap();
+
+    assert_eq!(read.scalar("id"), Some([0.0, 1.0].as_slice()));
+    assert_eq!(read.time, 2.5);
+}
+
+#[test]
+fn empty_frame_round_trips() {
+    let frame = ParticleFrame { time: 1.0, ..Default::default() };
+
+    assert_eq!(read_geo(write(&frame).as_bytes()).unwrap(), frame);
+}
+
+#[test]
+fn huge_point_count_fails_instead_of_allocating() {
+    let geo = "PGEOMETRY V5\nNPoints 99999999999 NPrims 0\nNPointGroups 0 NPrimGroups 0\nNPointAttrib 1 NVertexAttrib 0 NPrimAttrib 0 NAttrib 0\nPointAttrib\nid 99999999999 int 0\n0 0 0 1 (0)\n";
+
+    match read_geo(geo.as_bytes()) {
+        Err(ParticleFileError::Parse { .. }) => {}
+        result => panic!("unexpected result {result:?}"),
+    }
+}
+
+#[test]
+fn cache_lists_only_its_own_frames() {
+    let directory = common::temp_dir("geo-cache");
+    let cache = GeoCache::new(directory.path(), "fluid");
+    for number in [12, 2, 100] {
+        cache.write_frame(number, &frame()).unwrap();
+    }
+    GeoCache::new(directory.path(), "fluid2").write_frame(7, &frame()).unwrap();
+    std::fs::write(directory.join("fluid.geo"), "").unwrap();
+
+    assert_eq!(cache.frame_numbers().unwrap(), [2, 12, 100]);
+    assert_eq!(cache.read_frame(12).unwrap().positions, frame().positions);
+}
+
+#[test]
+fn names_with_whitespace_are_sanitized() {
+    let frame = frame().with_attribute("rest density", AttributeValues::Scalar(vec![1000.0, 998.0]));
+    let read = read_geo(write(&frame).as_bytes()).unwrap();
+
+    assert_eq!(read.scalar("rest_density"), Some([1000.0, 998.0].as_slice()));
+    assert_eq!(read.vector(ParticleFrame::VELOCITY), frame.vector(ParticleFrame::VELOCITY));
+}
@@ -0,0 +1,95 @@
+use glam::{vec3, Mat4, Vec3};
+use fluid_renderer::{create_from_mesh, Geometry, MeshSampling, TriangleMesh};
+
+
+/// Unit cube around the origin, every face split into two triangles along a diagonal
+fn cube() -> TriangleMesh {
+    Geometry::cube().to_triangle_mesh()
+}
+
+#[test]
+fn cube_contains_its_interior() {
+    let cube = cube();
+
+    for point in [Vec3::ZERO, vec3(0.25, 0.1, -0.3), vec3(-0.49, 0.49, 0.49), vec3(0.49, -0.49, -0.49)] {
+        assert!(cube.contains(point), "{point} should be inside");
+    }
+    for point in [vec3(0.6, 0.0, 0.0), vec3(-0.6, 0.0, 0.0), vec3(0.0, 0.6, 0.0), vec3(0.0, 0.0, -0.6), vec3(2.0, 2.0, 2.0)] {
+        assert!(!cube.contains(point), "{point} should be outside");
+    }
+}
+
+#[test]
+fn rays_through_shared_edges_count_once() {
+    let cube = cube();
+
+    // With y == z the ray runs exactly along the diagonal edge splitting the x faces,
+    // counting both triangles would flip inside and outside
+    for t in [-0.4, -0.25, 0.0, 0.25, 0.4] {
+        assert!(cube.contains(vec3(0.0, t, t)), "({t}, {t}) on the diagonal should be inside");
+        assert!(!cube.contains(vec3(0.75, t, t)), "({t}, {t}) past the cube should be outside");
+    }
+    // Through the edges and corners where faces meet
+    assert!(cube.contains(vec3(0.0, 0.5 - 1e-3, 0.5 - 1e-3)));
+    assert!(!cube.contains(vec3(0.0, 0.5 + 1e-3, 0.0)));
+}
+
+#[test]
+fn transformed_cube_moves_its_interior() {
+    let cube = cube().transformed(Mat4::from_scale_rotation_translation(Vec3::splat(2.0), Default::default(), vec3(5.0, 0.0, 0.0)));
+
+    assert!(cube.contains(vec3(5.9, 0.9, -0.9)));
+    assert!(!cube.contains(Vec3::ZERO));
+}
+
+#[test]
+fn interior_sampling_fills_the_cube_on_a_lattice() {
+    let spacing = 0.25;
+    let instances = create_from_mesh(&cube(), MeshSampling::Interior, spacing, Vec3::ONE);
+
+    // Four voxel centers per axis, -0.375 to 0.375
+    assert_eq!(instances.len(), 64);
+    for instance in &instances {
+        let position = instance.position;
+        assert!(position.abs().max_element() < 0.5, "{position} isn't inside");
+        let voxel = (position + 0.375) / spacing;
+        assert!((voxel - voxel.round()).abs().max_element() < 1e-4, "{position} isn't on the lattice");
+    }
+}
+
+#[test]
+fn surface_sampling_covers_every_face() {
+    let spacing = 0.1;
+    let instances = create_from_mesh(&cube(), MeshSampling::Surface, spacing, Vec3::ONE);
+
+    for instance in &instances {
+        let distance_to_surface = (instance.position.abs().max_element() - 0.5).abs();
+        assert!(distance_to_surface < 1e-4, "{} isn't on the surface", instance.position);
+    }
+
+    for normal in [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z] {
+        let on_face = instances.iter().filter(|instance| (instance.position.dot(normal) - 0.5).abs() < 1e-4).count();
+        // A face is 10 by 10 spacings, at least one particle per cell
+        assert!(on_face >= 100, "{on_face} particles on the {normal} face");
+    }
+
+    // Thinned to one particle per spacing cell
+    let mut cells = instances.iter()
+        .map(|instance| (instance.position / spacing).round().as_ivec3().to_array())
+        .collect::<Vec<_>>();
+    let count = cells.len();
+    cells.sort();
+    cells.dedup();
+    assert_eq!(cells.len(), count);
+}
+
+#[test]
+fn non_positive_spacing_gives_no_particles() {
+    let cube = cube();
+
+    for spacing in [0.0, -0.1, f32::NAN] {
+        for sampling in [MeshSampling::Interior, MeshSampling::Surface] {
+            assert!(create_from_mesh(&cube, sampling, spacing, Vec3::ONE).is_empty());
+        }
+    }
+}
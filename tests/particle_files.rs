@@ -0,0 +1,69 @@
+use glam::vec3;
+use fluid_renderer::{
+    read_csv, read_ply, write_csv, write_ply, AttributeValues, ParticleFileError, ParticleFrame, PlyFormat,
+};
+
+
+/// Colors, a vector and a scalar attribute, with values that don't print short
+fn frame() -> ParticleFrame {
+    let positions = vec![vec3(0.1, -2.5, 3.0), vec3(1e-7, 12345.678, -0.333), vec3(0.0, 0.0, 0.0)];
+    ParticleFrame { positions, time: 1.25, attributes: Vec::new() }
+        .with_attribute(ParticleFrame::COLOR, AttributeValues::Vector(vec![vec3(1.0, 0.5, 0.0), vec3(0.2, 0.4, 0.6), vec3(0.0, 0.0, 1.0)]))
+        .with_attribute(ParticleFrame::VELOCITY, AttributeValues::Vector(vec![vec3(-1.0, 0.0, 9.81), vec3(0.1, 0.2, 0.3), vec3(0.0, -0.0, 1e10)]))
+        .with_attribute(ParticleFrame::DENSITY, AttributeValues::Scalar(vec![1000.0, 999.123, f32::MIN_POSITIVE]))
+}
+
+#[test]
+fn ply_round_trips_in_every_format() {
+    let frame = frame();
+
+    for format in [PlyFormat::Ascii, PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian] {
+        let mut data = Vec::new();
+        write_ply(&mut data, &frame, format).unwrap();
+
+        assert_eq!(read_ply(data.as_slice()).unwrap(), frame, "{format:?}");
+    }
+}
+
+#[test]
+fn csv_round_trips_without_the_time() {
+    let frame = frame();
+    let mut data = Vec::new();
+    write_csv(&mut data, &frame).unwrap();
+
+    let header = String::from_utf8(data.clone()).unwrap().lines().next().unwrap().to_owned();
+    assert_eq!(header, "x,y,z,red,green,blue,velocity_x,velocity_y,velocity_z,density");
+    assert_eq!(read_csv(data.as_slice()).unwrap(), ParticleFrame { time: 0.0, ..frame });
+}
+
+#[test]
+fn names_with_separators_are_sanitized() {
+    let frame = ParticleFrame { positions: vec![vec3(1.0, 2.0, 3.0)], time: 0.0, attributes: Vec::new() }
+        .with_attribute("rest density", AttributeValues::Scalar(vec![1000.0]))
+        .with_attribute("a,b", AttributeValues::Scalar(vec![0.5]));
+    let expected = ParticleFrame { positions: vec![vec3(1.0, 2.0, 3.0)], time: 0.0, attributes: Vec::new() }
+        .with_attribute("rest_density", AttributeValues::Scalar(vec![1000.0]))
+        .with_attribute("a_b", AttributeValues::Scalar(vec![0.5]));
+
+    for format in [PlyFormat::Ascii, PlyFormat::BinaryLittleEndian] {
+        let mut data = Vec::new();
+        write_ply(&mut data, &frame, format).unwrap();
+        assert_eq!(read_ply(data.as_slice()).unwrap(), expected, "{format:?}");
+    }
+
+    let mut data = Vec::new();
+    write_csv(&mut data, &frame).unwrap();
+    assert_eq!(read_csv(data.as_slice()).unwrap(), expected);
+}
+
+#[test]
+fn huge_ply_vertex_count_fails_instead_of_allocating() {
+    let header = "ply\nformat binary_little_endian 1.0\nelement vertex 99999999999\nproperty float x\nproperty float y\nproperty float z\nend_header\n";
+    let mut data = header.as_bytes().to_vec();
+    data.extend([1.0_f32, 2.0, 3.0].iter().flat_map(|value| value.to_le_bytes()));
+
+    match read_ply(data.as_slice()) {
+        Err(ParticleFileError::Parse { message, .. }) => assert!(message.contains("ended early"), "{message}"),
+        result => panic!("unexpected result {result:?}"),
+    }
+}
@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+use glam::Vec3;
+use fluid_renderer::{Instance, Playback, Solver, SolverParams, PLAYBACK_SPEEDS};
+
+
+/// Exact in binary, so accumulated times don't drift
+const TIMESTEP: f32 = 0.25;
+
+/// Calls `advance` at the given seconds after a common start
+fn advance_at(playback: &mut Playback, start: Instant, seconds: f32) -> u32 {
+    playback.advance(start + Duration::from_secs_f32(seconds), TIMESTEP)
+}
+
+#[test]
+fn real_time_is_spent_in_fixed_steps() {
+    let mut playback = Playback::default();
+    let start = Instant::now();
+
+    // Nothing has passed before the first update
+    assert_eq!(advance_at(&mut playback, start, 0.0), 0);
+
+    assert_eq!(advance_at(&mut playback, start, 0.125), 0);
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 0.5);
+
+    assert_eq!(advance_at(&mut playback, start, 0.375), 1);
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 0.5);
+
+    assert_eq!(advance_at(&mut playback, start, 0.875), 2);
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 0.5);
+}
+
+#[test]
+fn slow_frames_are_clamped_to_the_step_limit() {
+    let mut playback = Playback::default();
+    playback.max_steps_per_update = 4;
+    let start = Instant::now();
+    advance_at(&mut playback, start, 0.0);
+
+    assert_eq!(advance_at(&mut playback, start, 10.125), 4);
+    // The dropped time is gone, only the partial step remains
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 0.5);
+    assert_eq!(advance_at(&mut playback, start, 10.25), 1);
+}
+
+#[test]
+fn speed_scales_the_simulated_time() {
+    let mut playback = Playback::default();
+    playback.speed = 2.0;
+    let start = Instant::now();
+    advance_at(&mut playback, start, 0.0);
+
+    assert_eq!(advance_at(&mut playback, start, 0.5), 4);
+}
+
+#[test]
+fn paused_playback_only_runs_requested_steps() {
+    let mut playback = Playback::default();
+    let start = Instant::now();
+    advance_at(&mut playback, start, 0.0);
+    assert_eq!(advance_at(&mut playback, start, 0.125), 0);
+
+    playback.toggle_pause();
+    assert_eq!(advance_at(&mut playback, start, 2.0), 0);
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 1.0);
+
+    playback.step();
+    playback.step();
+    assert!(playback.paused);
+    assert_eq!(advance_at(&mut playback, start, 2.125), 2);
+    assert_eq!(advance_at(&mut playback, start, 2.25), 0);
+
+    // Time spent paused isn't caught up on
+    playback.toggle_pause();
+    assert_eq!(advance_at(&mut playback, start, 2.375), 0);
+    assert_eq!(advance_at(&mut playback, start, 2.5), 1);
+}
+
+#[test]
+fn interpolation_can_be_disabled() {
+    let mut playback = Playback::default();
+    playback.interpolate = false;
+    let start = Instant::now();
+    advance_at(&mut playback, start, 0.0);
+    advance_at(&mut playback, start, 0.125);
+
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 1.0);
+}
+
+#[test]
+fn speed_steps_through_the_presets() {
+    let mut playback = Playback::default();
+
+    playback.change_speed(true);
+    assert_eq!(playback.speed, 2.0);
+
+    for _ in 0..PLAYBACK_SPEEDS.len() {
+        playback.change_speed(true);
+    }
+    assert_eq!(playback.speed, PLAYBACK_SPEEDS[PLAYBACK_SPEEDS.len() - 1]);
+
+    for _ in 0..PLAYBACK_SPEEDS.len() {
+        playback.change_speed(false);
+    }
+    assert_eq!(playback.speed, PLAYBACK_SPEEDS[0]);
+}
+
+#[test]
+fn rewind_restores_the_recorded_steps() {
+    let mut playback = Playback::default();
+    playback.history_capacity = 2;
+    let mut instances = vec![Instance { position: Vec3::ZERO, color: Vec3::ONE }];
+    let mut solver = Solver::new(SolverParams::default(), instances.len());
+
+    for _ in 0..3 {
+        playback.record(&instances, &solver);
+        solver.step(&mut instances);
+    }
+    assert_eq!(playback.history.len(), 2);
+    let after_one_step = playback.history[0].instances[0].position;
+
+    assert!(playback.rewind(&mut instances, &mut solver));
+    assert!(playback.rewind(&mut instances, &mut solver));
+    assert!(playback.paused);
+    assert_eq!(solver.steps, 1);
+    assert_eq!(instances[0].position, after_one_step);
+
+    // The oldest step fell out of the history
+    assert!(!playback.rewind(&mut instances, &mut solver));
+}
+
+#[test]
+fn fixed_advance_keeps_every_step() {
+    let mut playback = Playback::default();
+    playback.max_steps_per_update = 2;
+
+    // A video frame of 1.125 s, more steps than an update may run in real time
+    assert_eq!(playback.advance_by(1.125, TIMESTEP), 4);
+    assert_eq!(playback.interpolation_alpha(TIMESTEP), 0.5);
+    assert_eq!(playback.advance_by(1.125, TIMESTEP), 5);
+
+    playback.speed = 0.5;
+    assert_eq!(playback.advance_by(1.0, TIMESTEP), 2);
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use fluid_renderer::{
+    wgpu, GraphResources, RenderGraph, RenderGraphError, RenderNode, RenderTargetDescriptor,
+    RenderTargetRegistry, State, SURFACE,
+};
+
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+#[derive(Default)]
+struct TestNode {
+    label: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+    transients: Vec<&'static str>,
+}
+
+impl TestNode {
+    fn new(label: &'static str, reads: &[&'static str], writes: &[&'static str]) -> Self {
+        TestNode { label, reads: reads.to_vec(), writes: writes.to_vec(), ..Default::default() }
+    }
+
+    fn with_transients(self, transients: &[&'static str]) -> Self {
+        TestNode { transients: transients.to_vec(), ..self }
+    }
+}
+
+impl RenderNode for TestNode {
+    fn label(&self) -> &str {
+        self.label
+    }
+
+    fn reads(&self) -> Vec<String> {
+        self.reads.iter().map(|resource| resource.to_string()).collect()
+    }
+
+    fn writes(&self) -> Vec<String> {
+        self.writes.iter().map(|resource| resource.to_string()).collect()
+    }
+
+    fn transients(&self) -> Vec<RenderTargetDescriptor> {
+        self.transients.iter().map(|label| RenderTargetDescriptor::new(label, FORMAT)).collect()
+    }
+
+    fn record(&mut self, _: &State, _: &GraphResources, _: &mut wgpu::CommandEncoder) {}
+}
+
+fn labels(graph: &RenderGraph) -> Vec<&str> {
+    graph.node_labels().collect()
+}
+
+#[test]
+fn readers_run_after_writers_and_ties_keep_insertion_order() {
+    let Some((device, _queue)) = common::device() else { return };
+    let mut targets = RenderTargetRegistry::new((4, 4));
+    let mut graph = RenderGraph::new();
+
+    // Added in reverse, the sort has to move the producers in front
+    graph.add_node(TestNode::new("composite", &["scene", "bloom"], &[SURFACE]).with_transients(&["scene", "bloom"]), &mut targets, &device).unwrap();
+    graph.add_node(TestNode::new("bloom", &["scene"], &["bloom"]), &mut targets, &device).unwrap();
+    graph.add_node(TestNode::new("particles", &[], &["scene"]), &mut targets, &device).unwrap();
+    graph.add_node(TestNode::new("meshes", &[], &["scene"]), &mut targets, &device).unwrap();
+    graph.add_node(TestNode::new("hud", &[], &[SURFACE]), &mut targets, &device).unwrap();
+
+    assert_eq!(labels(&graph), ["particles", "meshes", "bloom", "composite", "hud"]);
+}
+
+#[test]
+fn cycles_are_rejected_and_leave_the_graph_unchanged() {
+    let Some((device, _queue)) = common::device() else { return };
+    let mut targets = RenderTargetRegistry::new((4, 4));
+    let mut graph = RenderGraph::new();
+
+    graph.add_node(TestNode::new("a", &["y"], &["x"]).with_transients(&["x", "y"]), &mut targets, &device).unwrap();
+    let error = graph.add_node(TestNode::new("b", &["x"], &["y"]), &mut targets, &device).unwrap_err();
+
+    match error {
+        RenderGraphError::Cycle(mut nodes) => {
+            nodes.sort();
+            assert_eq!(nodes, ["a", "b"]);
+        }
+        error => panic!("unexpected error {error}"),
+    }
+    assert_eq!(labels(&graph), ["a"]);
+}
+
+#[test]
+fn unknown_resources_and_duplicates_are_rejected() {
+    let Some((device, _queue)) = common::device() else { return };
+    let mut targets = RenderTargetRegistry::new((4, 4));
+    let mut graph = RenderGraph::new();
+
+    let error = graph.add_node(TestNode::new("blur", &["missing"], &[SURFACE]), &mut targets, &device).unwrap_err();
+    assert!(matches!(
+        &error,
+        RenderGraphError::UnknownResource { node, resource } if node == "blur" && resource == "missing",
+    ), "{error}");
+    assert_eq!(labels(&graph), Vec::<&str>::new());
+
+    graph.add_node(TestNode::new("blur", &[], &[SURFACE]), &mut targets, &device).unwrap();
+    let error = graph.add_node(TestNode::new("blur", &[], &[SURFACE]), &mut targets, &device).unwrap_err();
+    assert!(matches!(error, RenderGraphError::DuplicateNode(node) if node == "blur"));
+}
+
+#[test]
+fn removing_a_node_frees_its_transients() {
+    let Some((device, _queue)) = common::device() else { return };
+    let mut targets = RenderTargetRegistry::new((4, 4));
+    let mut graph = RenderGraph::new();
+
+    let imported = targets.register(&device, RenderTargetDescriptor::new("imported", FORMAT));
+    graph.import("imported", imported);
+    graph.add_node(TestNode::new("a", &["imported"], &["shared", "own"]).with_transients(&["shared", "own"]), &mut targets, &device).unwrap();
+    graph.add_node(TestNode::new("b", &["shared"], &[SURFACE]).with_transients(&["shared"]), &mut targets, &device).unwrap();
+
+    let shared = graph.resource("shared").unwrap();
+    let own = graph.resource("own").unwrap();
+    assert!(graph.remove_node("a", &mut targets).is_some());
+
+    assert!(!targets.contains(own));
+    assert_eq!(graph.resource("own"), None);
+    // Still declared by `b`
+    assert!(targets.contains(shared));
+    assert_eq!(labels(&graph), ["b"]);
+
+    assert!(graph.remove_node("b", &mut targets).is_some());
+    assert!(!targets.contains(shared));
+    // Imported targets aren't the graph's to free
+    assert!(targets.contains(imported));
+    assert_eq!(graph.resource("imported"), Some(imported));
+}
+
+#[test]
+fn failed_add_frees_the_transients_it_allocated() {
+    let Some((device, _queue)) = common::device() else { return };
+    let mut targets = RenderTargetRegistry::new((4, 4));
+    let mut graph = RenderGraph::new();
+
+    let error = graph.add_node(TestNode::new("a", &["missing"], &["scratch"]).with_transients(&["scratch"]), &mut targets, &device);
+    assert!(error.is_err());
+    assert_eq!(graph.resource("scratch"), None);
+}
@@ -0,0 +1,127 @@
+use glam::{vec3, Vec3};
+use fluid_renderer::{
+    create_cube, create_cylinder, create_poisson_disk, create_shell, create_sphere, create_torus, create_volume,
+    scene_rng, Volume, CUBE_DIMENSIONS, DEFAULT_SEED,
+};
+
+
+#[test]
+fn default_scene_is_pinned() {
+    let instances = create_cube(&mut scene_rng(DEFAULT_SEED), 0.1, CUBE_DIMENSIONS, None, (-1.0, -1.0, -2.0));
+    let positions = instances[..3].iter().map(|instance| instance.position).collect::<Vec<_>>();
+
+    // Changes when rand samples floats differently, the default scene and golden images change with it
+    assert_eq!(positions, [
+        vec3(-0.9487624, -0.94832736, -1.9981611),
+        vec3(-0.8502726, -0.9499411, -1.9984069),
+        vec3(-0.74951226, -0.9535186, -2.002437),
+    ]);
+}
+
+#[test]
+fn same_seed_same_scene() {
+    let cube = |seed| {
+        create_cube(&mut scene_rng(seed), 0.5, (4, 4, 4), None, (0.0, 0.0, 0.0))
+            .into_iter()
+            .map(|instance| instance.position)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(cube(7), cube(7));
+    assert_ne!(cube(7), cube(8));
+}
+
+#[test]
+fn poisson_disk_samples_keep_their_distance() {
+    let spacing = 0.1;
+    let volumes = [
+        Volume::Box { min: Vec3::ZERO, max: Vec3::splat(0.6) },
+        Volume::Sphere { center: Vec3::ZERO, radius: 0.4 },
+        Volume::Torus { center: Vec3::ZERO, major_radius: 0.5, minor_radius: 0.15 },
+    ];
+
+    for (seed, volume) in volumes.into_iter().enumerate() {
+        let instances = create_poisson_disk(&mut scene_rng(seed as u64), volume, spacing, Vec3::ONE);
+        assert!(instances.len() > 50, "{volume:?} only got {} samples", instances.len());
+
+        for (index, instance) in instances.iter().enumerate() {
+            assert!(volume.contains(instance.position), "{:?} outside {volume:?}", instance.position);
+            for other in &instances[index + 1..] {
+                let distance = instance.position.distance(other.position);
+                assert!(distance >= spacing, "samples {distance} apart in {volume:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn non_positive_spacing_gives_no_particles() {
+    let volume = Volume::Sphere { center: Vec3::ZERO, radius: 1.0 };
+
+    for spacing in [0.0, -0.1, f32::NAN] {
+        assert!(create_poisson_disk(&mut scene_rng(DEFAULT_SEED), volume, spacing, Vec3::ONE).is_empty());
+        assert!(create_volume(volume, spacing, Vec3::ONE).is_empty());
+    }
+}
+
+#[test]
+fn poisson_disk_grid_overflow_gives_no_particles() {
+    let volume = Volume::Box { min: Vec3::splat(-1000.0), max: Vec3::splat(1000.0) };
+
+    assert!(create_poisson_disk(&mut scene_rng(DEFAULT_SEED), volume, 1e-6, Vec3::ONE).is_empty());
+}
+
+#[test]
+fn sphere_count_matches_its_volume() {
+    let (radius, spacing) = (1.0, 0.1);
+    let instances = create_sphere(vec3(0.5, -2.0, 3.0), radius, spacing, Vec3::ONE);
+
+    let expected = 4.0 / 3.0 * std::f32::consts::PI * radius.powi(3) / spacing.powi(3);
+    let ratio = instances.len() as f32 / expected;
+    assert!((ratio - 1.0).abs() < 0.05, "{} particles, expected about {expected}", instances.len());
+}
+
+#[test]
+fn cylinder_stays_in_its_bounds() {
+    let center = vec3(0.0, 1.0, 0.0);
+    let instances = create_cylinder(center, 0.5, 2.0, 0.1, Vec3::ONE);
+    assert!(!instances.is_empty());
+
+    for instance in &instances {
+        let offset = instance.position - center;
+        assert!(vec3(offset.x, 0.0, offset.z).length() <= 0.5 + 1e-5 && offset.y.abs() <= 1.0 + 1e-5, "{offset:?}");
+    }
+}
+
+#[test]
+fn torus_has_a_hole() {
+    let (major_radius, minor_radius) = (0.5, 0.2);
+    let instances = create_torus(Vec3::ZERO, major_radius, minor_radius, 0.05, Vec3::ONE);
+    assert!(!instances.is_empty());
+
+    for instance in &instances {
+        let radial = vec3(instance.position.x, 0.0, instance.position.z).length();
+        assert!(radial >= major_radius - minor_radius - 1e-5, "{:?} in the hole", instance.position);
+    }
+}
+
+#[test]
+fn shell_keeps_the_outer_layers() {
+    let (spacing, layers) = (0.1, 2);
+    let volumes = [
+        Volume::Sphere { center: Vec3::ZERO, radius: 1.0 },
+        Volume::Box { min: Vec3::ZERO, max: vec3(1.0, 0.5, 2.0) },
+        Volume::Cylinder { center: Vec3::ZERO, radius: 0.5, height: 1.0 },
+    ];
+
+    for volume in volumes {
+        let shell = create_shell(volume, spacing, layers, Vec3::ONE);
+        assert!(!shell.is_empty());
+        assert!(shell.len() < create_volume(volume, spacing, Vec3::ONE).len());
+
+        for instance in &shell {
+            let distance = volume.signed_distance(instance.position);
+            assert!(-(layers as f32) * spacing < distance && distance <= 0.0, "{distance} in {volume:?}");
+        }
+    }
+}
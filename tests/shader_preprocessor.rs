@@ -0,0 +1,111 @@
+use std::path::Path;
+use fluid_renderer::{PreprocessErrorKind, ShaderPreprocessor};
+
+
+fn lines(source: &str) -> Vec<&str> {
+    source.lines().map(str::trim).filter(|line| !line.is_empty()).collect()
+}
+
+#[test]
+fn include_cycle_is_reported() {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor
+        .add_source("shaders/a.wgsl", "#include \"b.wgsl\"")
+        .add_source("shaders/b.wgsl", "#include \"a.wgsl\"");
+
+    let error = preprocessor.process_file("shaders/a.wgsl").unwrap_err();
+    match &error.kind {
+        PreprocessErrorKind::IncludeCycle { chain } => {
+            let names = chain.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect::<Vec<_>>();
+            assert_eq!(names, ["a.wgsl", "b.wgsl", "a.wgsl"]);
+        }
+        kind => panic!("unexpected error {kind:?}"),
+    }
+    let location = error.location.unwrap();
+    assert_eq!((location.file.as_path(), location.line), (Path::new("shaders/b.wgsl"), 1));
+}
+
+#[test]
+fn files_are_included_once() {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor
+        .add_source("common.wgsl", "const PI = 3.14;")
+        .add_source("lighting.wgsl", "#include \"common.wgsl\"\nfn light() {}")
+        .add_source("main.wgsl", "#include \"common.wgsl\"\n#include \"lighting.wgsl\"\n#include \"./common.wgsl\"\nfn main() {}");
+
+    let processed = preprocessor.process_file("main.wgsl").unwrap();
+    assert_eq!(lines(&processed.source), ["const PI = 3.14;", "fn light() {}", "fn main() {}"]);
+}
+
+#[test]
+fn nested_conditionals_follow_their_parent() {
+    let source = "
+        #ifdef OUTER
+            outer
+            #ifdef INNER
+                both
+            #else
+                outer_only
+            #endif
+        #else
+            #ifndef INNER
+                neither
+            #else
+                inner_only
+            #endif
+        #endif
+    ";
+    let process = |defines: &[&str]| {
+        let mut preprocessor = ShaderPreprocessor::new();
+        for define in defines {
+            preprocessor.enable(define);
+        }
+        preprocessor.process_str("nested.wgsl", source).unwrap().source
+    };
+
+    assert_eq!(lines(&process(&["OUTER", "INNER"])), ["outer", "both"]);
+    assert_eq!(lines(&process(&["OUTER"])), ["outer", "outer_only"]);
+    assert_eq!(lines(&process(&["INNER"])), ["inner_only"]);
+    assert_eq!(lines(&process(&[])), ["neither"]);
+}
+
+#[test]
+fn unbalanced_conditionals_are_reported() {
+    let preprocessor = ShaderPreprocessor::new();
+    let error = |source| preprocessor.process_str("unbalanced.wgsl", source).unwrap_err().kind;
+
+    assert!(matches!(error("#ifdef A\n#else\n#else\n#endif"), PreprocessErrorKind::UnexpectedElse));
+    assert!(matches!(error("#endif"), PreprocessErrorKind::UnexpectedEndif));
+    assert!(matches!(error("#ifdef A\n#ifdef B\n#endif"), PreprocessErrorKind::UnterminatedConditional));
+}
+
+#[test]
+fn defines_are_substituted_outside_comments() {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor.define("RADIUS", 0.5_f32);
+
+    let processed = preprocessor
+        .process_str("defines.wgsl", "let r = RADIUS * RADIUS_SCALE; // RADIUS stays")
+        .unwrap();
+    assert_eq!(processed.source.trim(), "let r = 0.5 * RADIUS_SCALE; // RADIUS stays");
+}
+
+#[test]
+fn offsets_map_back_to_included_files() {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor
+        .add_source("include.wgsl", "\nfn included() {}")
+        .add_source("main.wgsl", "fn first() {}\n#include \"include.wgsl\"\nfn last() {}");
+
+    let processed = preprocessor.process_file("main.wgsl").unwrap();
+    let locate = |needle: &str| {
+        let location = processed.locate_offset(processed.source.find(needle).unwrap()).unwrap();
+        (location.file.to_str().unwrap().to_owned(), location.line)
+    };
+
+    assert_eq!(locate("first"), ("main.wgsl".to_owned(), 1));
+    assert_eq!(locate("included"), ("include.wgsl".to_owned(), 2));
+    assert_eq!(locate("last"), ("main.wgsl".to_owned(), 3));
+    // Offsets past the end are clamped, which lands behind the trailing newline
+    assert_eq!(processed.locate_offset(usize::MAX), None);
+}
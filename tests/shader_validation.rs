@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+use fluid_renderer::{
+    shader_preprocessor, validate_wgsl, validate_wgsl_file, check_vertex_inputs,
+    Shader, ShaderPreprocessor, ShaderValidationError, Vertex, InstanceRaw, MeshVertex,
+};
+
+
+fn source_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("src")
+}
+
+fn wgsl_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            wgsl_files(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "wgsl") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn every_shader_validates() {
+    let mut files = Vec::new();
+    wgsl_files(&source_dir(), &mut files);
+    assert!(!files.is_empty());
+
+    for file in files {
+        if let Err(error) = validate_wgsl_file(&file, &shader_preprocessor()) {
+            panic!("{}: {error}", file.display());
+        }
+    }
+}
+
+#[test]
+fn particle_shader_validates_without_features() {
+    let mut preprocessor = shader_preprocessor();
+    preprocessor.undefine("INSTANCE_COLOR");
+
+    validate_wgsl_file(source_dir().join("shader.wgsl"), &preprocessor).unwrap();
+}
+
+#[test]
+fn particle_shader_matches_vertex_layouts() {
+    let (module, _) = validate_wgsl_file(source_dir().join("shader.wgsl"), &shader_preprocessor()).unwrap();
+
+    check_vertex_inputs(&module, "vs_main", &[Vertex::desc(), InstanceRaw::desc()]).unwrap();
+}
+
+#[test]
+fn mesh_shader_matches_vertex_layout() {
+    let (module, _) = validate_wgsl_file(source_dir().join("shaders/mesh.wgsl"), &shader_preprocessor()).unwrap();
+
+    check_vertex_inputs(&module, "vs_mesh", &[MeshVertex::desc()]).unwrap();
+}
+
+#[test]
+fn missing_vertex_buffer_is_reported() {
+    let (module, _) = validate_wgsl_file(source_dir().join("shader.wgsl"), &shader_preprocessor()).unwrap();
+
+    let error = check_vertex_inputs(&module, "vs_main", &[Vertex::desc()]).unwrap_err();
+    assert!(matches!(error, ShaderValidationError::VertexInput { location: 5, .. }), "{error}");
+}
+
+#[test]
+fn mismatched_vertex_format_is_reported() {
+    let source = "
+        @vertex
+        fn vs_main(@location(0) position: vec4<f32>) -> @builtin(position) vec4<f32> {
+            return position;
+        }
+    ";
+    let shader = ShaderPreprocessor::new().process_str("inline.wgsl", source).unwrap();
+    let (module, _) = validate_wgsl(&shader).unwrap();
+
+    let error = check_vertex_inputs(&module, "vs_main", &[Vertex::desc()]).unwrap_err();
+    assert!(matches!(error, ShaderValidationError::VertexInput { location: 0, .. }), "{error}");
+}
+
+#[test]
+fn errors_map_back_to_original_lines() {
+    let source = "#ifdef MISSING\nthis is skipped\n#endif\nfn broken() -> f32 {\n    return undefined_value;\n}\n";
+    let shader = ShaderPreprocessor::new().process_str("broken.wgsl", source).unwrap();
+
+    match validate_wgsl(&shader).unwrap_err() {
+        ShaderValidationError::Parse { location: Some(location), .. } => {
+            assert_eq!(location.file, Path::new("broken.wgsl"));
+            assert_eq!(location.line, 5);
+        }
+        error => panic!("unexpected error: {error}"),
+    }
+}
+
+#[test]
+fn shader_errors_point_into_includes() {
+    let mut preprocessor = ShaderPreprocessor::new();
+    preprocessor
+        .add_source("lib.wgsl", "fn helper() -> f32 {\n    return 1.0;\n}\n\nfn broken() -> f32 {\n    return missing;\n}")
+        .add_source("main.wgsl", "#include \"lib.wgsl\"\n@vertex\nfn vs_main() -> @builtin(position) vec4<f32> {\n    return vec4(helper());\n}");
+
+    let error = Shader::with_preprocessor("main.wgsl", preprocessor).err().unwrap().to_string();
+    assert!(error.contains("lib.wgsl:6"), "{error}");
+}
@@ -0,0 +1,55 @@
+use glam::Vec3;
+use fluid_renderer::{wgpu, Geometry, Indices};
+
+
+#[test]
+fn compact_indices_switch_to_u32_past_u16_max() {
+    let largest_u16 = Indices::compact(vec![0, 1, u16::MAX as u32]);
+    assert_eq!(largest_u16, Indices::U16(vec![0, 1, u16::MAX]));
+    assert_eq!(largest_u16.format(), wgpu::IndexFormat::Uint16);
+    assert_eq!(largest_u16.as_bytes().len(), 6);
+
+    let past_u16 = Indices::compact(vec![0, 1, u16::MAX as u32 + 1]);
+    assert_eq!(past_u16, Indices::U32(vec![0, 1, 65536]));
+    assert_eq!(past_u16.format(), wgpu::IndexFormat::Uint32);
+    assert_eq!(past_u16.as_bytes().len(), 12);
+    assert_eq!(past_u16.to_u32(), [0, 1, 65536]);
+}
+
+/// Every triangle's counter-clockwise normal has to agree with the normals of its vertices
+fn assert_winds_outward(name: &str, geometry: &Geometry) {
+    let mesh = geometry.to_triangle_mesh();
+    assert!(!mesh.indices.is_empty(), "{name} has no triangles");
+
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| mesh.positions[triangle[corner] as usize]);
+        let face_normal = (b - a).cross(c - a);
+        let vertex_normal: Vec3 = triangle.iter().map(|index| mesh.normals[*index as usize]).sum();
+
+        assert!(face_normal.length() > 1e-9, "{name} has a degenerate triangle {triangle:?}");
+        assert!(face_normal.dot(vertex_normal) > 0.0, "{name} triangle {triangle:?} winds inward");
+    }
+}
+
+#[test]
+fn solids_wind_counter_clockwise_seen_from_outside() {
+    assert_winds_outward("cube", &Geometry::cube());
+    assert_winds_outward("uv sphere", &Geometry::uv_sphere(16, 8));
+    assert_winds_outward("coarse uv sphere", &Geometry::uv_sphere(3, 2));
+    assert_winds_outward("icosahedron", &Geometry::icosphere(0));
+    assert_winds_outward("icosphere", &Geometry::icosphere(2));
+}
+
+#[test]
+fn flat_shapes_face_positive_z() {
+    assert_winds_outward("triangle", &Geometry::polygon(3));
+    assert_winds_outward("hexagon", &Geometry::polygon(6));
+    assert_winds_outward("circle", &Geometry::circle(24));
+}
+
+#[test]
+fn dense_shapes_use_u32_indices() {
+    // 256 * 256 vertices still fit in u16, one more row doesn't
+    assert_eq!(Geometry::uv_sphere(255, 255).indices.format(), wgpu::IndexFormat::Uint16);
+    assert_eq!(Geometry::uv_sphere(255, 256).indices.format(), wgpu::IndexFormat::Uint32);
+}
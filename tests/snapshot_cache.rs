@@ -0,0 +1,107 @@
+mod common;
+
+use common::TempDir;
+use glam::vec3;
+use fluid_renderer::{AttributeValues, ParticleFrame, SnapshotCache};
+
+
+/// Fresh directory with a csv frame per name, the particle is at x = the name's index
+fn cache_directory(test: &str, names: &[&str]) -> TempDir {
+    let directory = common::temp_dir(&format!("cache-{test}"));
+
+    for (index, name) in names.iter().enumerate() {
+        let frame = ParticleFrame { positions: vec![vec3(index as f32, 0.0, 0.0)], time: 0.0, attributes: Vec::new() };
+        frame.save(directory.join(name)).unwrap();
+    }
+
+    directory
+}
+
+fn file_names(cache: &SnapshotCache) -> Vec<&str> {
+    cache.frames.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect()
+}
+
+#[test]
+fn sequences_are_ordered_by_their_trailing_number() {
+    let directory = cache_directory("order", &["name_00012.csv", "name_00002.csv", "name.12.csv", "name.2.csv", "rest.csv"]);
+    std::fs::write(directory.join("notes.txt"), "not a frame").unwrap();
+    let cache = SnapshotCache::open(directory.path()).unwrap();
+
+    // One sequence after the other, each numerically instead of by name
+    assert_eq!(file_names(&cache), ["name.2.csv", "name.12.csv", "name_00002.csv", "name_00012.csv", "rest.csv"]);
+}
+
+#[test]
+fn advance_loops_or_stops_on_the_last_frame() {
+    let directory = cache_directory("advance", &["f1.csv", "f2.csv", "f3.csv"]);
+    let mut cache = SnapshotCache::open(directory.path()).unwrap();
+
+    assert!(cache.advance(2));
+    assert_eq!(cache.current(), 2);
+    assert!(cache.advance(2));
+    assert_eq!(cache.current(), 1);
+
+    cache.looping = false;
+    assert!(cache.advance(1));
+    assert_eq!(cache.current(), 2);
+    assert!(!cache.advance(1));
+    assert_eq!(cache.current(), 2);
+    assert!(!cache.advance(7));
+    assert_eq!(cache.current(), 2);
+}
+
+#[test]
+fn step_back_wraps_only_when_looping() {
+    let directory = cache_directory("step-back", &["f1.csv", "f2.csv", "f3.csv"]);
+    let mut cache = SnapshotCache::open(directory.path()).unwrap();
+
+    cache.step_back();
+    assert_eq!(cache.current(), 2);
+    cache.step_back();
+    assert_eq!(cache.current(), 1);
+
+    cache.looping = false;
+    cache.seek(0);
+    cache.step_back();
+    assert_eq!(cache.current(), 0);
+}
+
+#[test]
+fn least_recently_used_frame_is_dropped() {
+    let names = ["f1.csv", "f2.csv", "f3.csv", "f4.csv"];
+    let directory = cache_directory("lru", &names);
+    let mut cache = SnapshotCache::open(directory.path()).unwrap();
+    for index in [0, 1, 2, 0] {
+        assert_eq!(cache.frame(index).unwrap().positions[0].x, index as f32);
+    }
+
+    // Loaded frames aren't read again, so removing the files shows which ones are kept
+    for name in names {
+        std::fs::remove_file(directory.join(name)).unwrap();
+    }
+    std::fs::write(directory.join("f4.csv"), "x,y,z\n3,0,0\n").unwrap();
+    cache.frame(3).unwrap();
+
+    assert!(cache.frame(0).is_ok());
+    assert!(cache.frame(2).is_ok());
+    assert!(cache.frame(1).is_err());
+}
+
+#[test]
+fn lerp_matches_particles_by_id() {
+    let frame = |positions: Vec<_>, ids: Vec<f32>| {
+        ParticleFrame { positions, time: 0.0, attributes: Vec::new() }
+            .with_attribute(ParticleFrame::ID, AttributeValues::Scalar(ids))
+    };
+    // Particle 1 died and 3 was born, the survivors are stored in another order
+    let from = frame(vec![vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0)], vec![0.0, 1.0, 2.0]);
+    let to = frame(vec![vec3(2.0, 2.0, 0.0), vec3(9.0, 9.0, 9.0), vec3(0.0, 2.0, 0.0)], vec![2.0, 3.0, 0.0]);
+
+    let blended = from.lerp(&to, 0.5);
+    assert_eq!(blended.positions, [vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(2.0, 1.0, 0.0)]);
+    assert_eq!(blended.scalar(ParticleFrame::ID), Some([0.0, 1.0, 2.0].as_slice()));
+
+    // Without ids on both sides they're matched by index
+    let unnamed = ParticleFrame { attributes: Vec::new(), ..to.clone() };
+    assert_eq!(from.lerp(&unnamed, 0.5).positions, [vec3(1.0, 1.0, 0.0), vec3(5.0, 4.5, 4.5), vec3(1.0, 1.0, 0.0)]);
+}
@@ -0,0 +1,81 @@
+use glam::{vec3, Vec3};
+use fluid_renderer::{Instance, Solver, SolverParams};
+
+
+fn lattice(params: &SolverParams, size: i32) -> Vec<Instance> {
+    let mut instances = Vec::new();
+    for z in -size..=size {
+        for y in -size..=size {
+            for x in -size..=size {
+                let position = vec3(x as f32, y as f32, z as f32) * params.particle_spacing;
+                instances.push(Instance { position, color: Vec3::ONE });
+            }
+        }
+    }
+
+    instances
+}
+
+#[test]
+fn lattice_at_particle_spacing_has_rest_density() {
+    let params = SolverParams::default();
+    let instances = lattice(&params, 3);
+    let center = instances.iter().position(|instance| instance.position == Vec3::ZERO).unwrap();
+
+    let mut solver = Solver::new(params, instances.len());
+    solver.find_neighbors(&instances);
+    solver.compute_densities(&instances);
+
+    let density = solver.densities[center];
+    assert!((density / params.rest_density - 1.0).abs() < 0.02, "density {density}");
+}
+
+#[test]
+fn pressure_is_never_negative() {
+    let params = SolverParams::default();
+    let instances = vec![Instance { position: Vec3::ZERO, color: Vec3::ONE }];
+
+    let mut solver = Solver::new(params, instances.len());
+    solver.find_neighbors(&instances);
+    solver.compute_densities(&instances);
+
+    // A lone particle is far below the rest density
+    assert!(solver.densities[0] < params.rest_density);
+    assert_eq!(solver.pressures[0], 0.0);
+}
+
+#[test]
+fn particles_bounce_off_the_bounds() {
+    let params = SolverParams {
+        gravity: Vec3::ZERO,
+        restitution: 0.5,
+        ..Default::default()
+    };
+    let start = params.bounds_min + Vec3::splat(0.5);
+    let mut instances = vec![Instance { position: vec3(params.bounds_min.x + 0.01, start.y, params.bounds_max.z - 0.01), color: Vec3::ONE }];
+
+    let mut solver = Solver::new(params, instances.len());
+    solver.velocities[0] = vec3(-10.0, 0.0, 10.0);
+    solver.step(&mut instances);
+
+    let position = instances[0].position;
+    assert_eq!(position.x, params.bounds_min.x);
+    assert_eq!(position.z, params.bounds_max.z);
+    assert_eq!(position.y, start.y);
+    assert_eq!(solver.velocities[0], vec3(5.0, 0.0, -5.0));
+}
+
+#[test]
+fn steps_advance_the_time() {
+    let params = SolverParams::default();
+    let mut instances = lattice(&params, 1);
+    let mut solver = Solver::new(params, instances.len());
+
+    solver.step(&mut instances);
+    solver.substep(&mut instances, params.timestep / 2.0);
+
+    assert_eq!(solver.steps, 2);
+    assert_eq!(solver.time, params.timestep * 1.5);
+    // Gravity pulls the whole lattice down
+    assert!(solver.velocities.iter().all(|velocity| velocity.y < 0.0));
+}
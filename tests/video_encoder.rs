@@ -0,0 +1,115 @@
+#![cfg(unix)]
+
+mod common;
+
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+use fluid_renderer::{CapturedFrame, Recorder, RecorderError, VideoCodec, VideoEncoder, VideoSettings};
+
+
+/// Executable shell script standing in for ffmpeg
+fn stub(dir: &Path, script: &str) -> PathBuf {
+    let path = dir.join("ffmpeg-stub");
+    std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn frame(width: u32, height: u32, value: u8) -> CapturedFrame {
+    CapturedFrame::new(width, height, vec![value; (width * height * 4) as usize])
+}
+
+#[test]
+fn missing_ffmpeg_is_reported() {
+    let settings = VideoSettings::new("out.mp4").program("fluid-renderer-no-such-ffmpeg");
+
+    match VideoEncoder::spawn(&settings, (4, 4)) {
+        Err(error @ RecorderError::FfmpegNotFound(_)) => {
+            let message = error.to_string();
+            assert!(message.contains("fluid-renderer-no-such-ffmpeg"), "{message}");
+            assert!(message.contains("PATH"), "{message}");
+        }
+        Err(error) => panic!("unexpected error {error}"),
+        Ok(_) => panic!("spawned a program that doesn't exist"),
+    }
+}
+
+#[test]
+fn frames_are_piped_as_raw_rgba() {
+    let dir = common::temp_dir("pipe");
+    // The output path is the last argument, the arguments go next to it
+    let program = stub(dir.path(), r#"for output; do :; done; echo "$@" > "$output.args"; cat > "$output""#);
+    let output = dir.join("video.webm");
+    let settings = VideoSettings::new(&output).framerate(24).crf(18).program(program);
+    assert_eq!(settings.codec, VideoCodec::Vp9);
+
+    let mut recorder = Recorder::video(&settings, (3, 2)).unwrap();
+    for value in 0..5 {
+        recorder.write_frame(&frame(3, 2, value)).unwrap();
+    }
+    assert_eq!(recorder.frames(), 5);
+    recorder.finish().unwrap();
+
+    let data = std::fs::read(&output).unwrap();
+    let expected = (0..5).flat_map(|value| frame(3, 2, value).data).collect::<Vec<_>>();
+    assert_eq!(data, expected);
+
+    let arguments = std::fs::read_to_string(dir.join("video.webm.args")).unwrap();
+    for expected in ["-f rawvideo -pix_fmt rgba", "-s 3x2", "-r 24", "-i -", "-c:v libvpx-vp9", "-crf 18", "-b:v 0"] {
+        assert!(arguments.contains(expected), "`{expected}` missing from `{arguments}`");
+    }
+}
+
+#[test]
+fn encoder_failure_includes_its_output() {
+    let dir = common::temp_dir("failure");
+    let program = stub(dir.path(), "echo 'Unknown encoder libx264' >&2; exit 1");
+    let settings = VideoSettings::new(dir.join("video.mp4")).program(program);
+
+    let mut encoder = VideoEncoder::spawn(&settings, (64, 64)).unwrap();
+    // The stub may exit before or after reading, the error shows up on a write or when finishing
+    let result = (0..16)
+        .try_for_each(|_| encoder.write_frame(&frame(64, 64, 0)))
+        .and_then(|()| encoder.finish());
+
+    match result {
+        Err(error @ RecorderError::Ffmpeg { .. }) => {
+            let message = error.to_string();
+            assert!(message.contains("Unknown encoder libx264"), "{message}");
+        }
+        Err(error) => panic!("unexpected error {error}"),
+        Ok(()) => panic!("a failing encoder reported success"),
+    }
+}
+
+#[test]
+fn frame_size_has_to_match_the_video() {
+    let dir = common::temp_dir("size");
+    let program = stub(dir.path(), "cat > /dev/null");
+    let settings = VideoSettings::new(dir.join("video.mp4")).program(program);
+
+    let mut recorder = Recorder::video(&settings, (4, 4)).unwrap();
+    assert!(matches!(
+        recorder.write_frame(&frame(8, 4, 0)),
+        Err(RecorderError::FrameSize { expected: (4, 4), found: (8, 4) }),
+    ));
+    recorder.finish().unwrap();
+}
+
+#[test]
+fn png_sequence_round_trips() {
+    let dir = common::temp_dir("png");
+    let mut recorder = Recorder::png_sequence(dir.path()).unwrap();
+    let frames = [frame(5, 3, 10), frame(5, 3, 200)];
+    for frame in &frames {
+        recorder.write_frame(frame).unwrap();
+    }
+    recorder.finish().unwrap();
+
+    for (index, frame) in frames.iter().enumerate() {
+        let loaded = CapturedFrame::load_png(dir.join(format!("frame_{index:05}.png"))).unwrap();
+        assert_eq!(&loaded, frame);
+    }
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use glam::vec3;
+use fluid_renderer::{write_pvd, write_vtk, write_vtu, AttributeValues, ParticleFrame, VtkFormat, VtkSeries};
+
+
+fn frame() -> ParticleFrame {
+    let positions = vec![vec3(0.0, 1.0, 2.0), vec3(-1.5, 0.25, 3.0)];
+    ParticleFrame { positions, time: 0.5, attributes: Vec::new() }
+        .with_attribute("pressure", AttributeValues::Scalar(vec![10.0, 20.5]))
+        .with_attribute("velocity", AttributeValues::Vector(vec![vec3(1.0, 0.0, 0.0), vec3(0.0, -2.0, 0.0)]))
+}
+
+fn to_string(write: impl FnOnce(&mut Vec<u8>) -> std::io::Result<()>) -> String {
+    let mut output = Vec::new();
+    write(&mut output).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+/// Lines after the first one equal to `header`
+fn section<'a>(output: &'a str, header: &str, lines: usize) -> Vec<&'a str> {
+    output.lines()
+        .skip_while(|line| *line != header)
+        .skip(1)
+        .take(lines)
+        .collect()
+}
+
+#[test]
+fn legacy_vtk_has_points_cells_and_attributes() {
+    let output = to_string(|output| write_vtk(output, &frame()));
+
+    assert!(output.starts_with("# vtk DataFile Version 3.0\n"));
+    assert_eq!(section(&output, "TIME 1 1 float", 1), ["0.5"]);
+    assert_eq!(section(&output, "POINTS 2 float", 2), ["0 1 2", "-1.5 0.25 3"]);
+    assert_eq!(section(&output, "VERTICES 2 4", 2), ["1 0", "1 1"]);
+    assert_eq!(section(&output, "POINT_DATA 2", 4), ["SCALARS pressure float 1", "LOOKUP_TABLE default", "10", "20.5"]);
+    assert_eq!(section(&output, "VECTORS velocity float", 2), ["1 0 0", "0 -2 0"]);
+}
+
+#[test]
+fn legacy_vtk_names_have_no_whitespace() {
+    let frame = frame().with_attribute("rest density", AttributeValues::Scalar(vec![1.0, 2.0]));
+    let output = to_string(|output| write_vtk(output, &frame));
+
+    assert!(output.contains("SCALARS rest_density float 1\n"), "{output}");
+}
+
+#[test]
+fn vtu_has_points_cells_and_attributes() {
+    let output = to_string(|output| write_vtu(output, &frame()));
+
+    assert!(output.contains(r#"<Piece NumberOfPoints="2" NumberOfCells="2">"#));
+    assert!(output.contains(r#"NumberOfTuples="1" format="ascii">0.5</DataArray>"#));
+    assert_eq!(section(&output, r#"        <DataArray type="Float32" NumberOfComponents="3" format="ascii">"#, 2), ["0 1 2", "-1.5 0.25 3"]);
+    assert_eq!(section(&output, r#"        <DataArray type="Int64" Name="offsets" format="ascii">"#, 2), ["1", "2"]);
+    assert_eq!(section(&output, r#"        <DataArray type="Float32" Name="pressure" NumberOfComponents="1" format="ascii">"#, 2), ["10", "20.5"]);
+    assert_eq!(section(&output, r#"        <DataArray type="Float32" Name="velocity" NumberOfComponents="3" format="ascii">"#, 2), ["1 0 0", "0 -2 0"]);
+    assert!(output.trim_end().ends_with("</VTKFile>"));
+}
+
+#[test]
+fn vtu_escapes_attribute_names() {
+    let frame = frame().with_attribute(r#"a<b & "c""#, AttributeValues::Scalar(vec![1.0, 2.0]));
+    let output = to_string(|output| write_vtu(output, &frame));
+
+    assert!(output.contains(r#"Name="a&lt;b &amp; &quot;c&quot;""#), "{output}");
+    assert!(!output.contains("a<b"));
+}
+
+#[test]
+fn pvd_lists_every_file() {
+    let files = [(0.0, "frame_00000.vtu".to_owned()), (0.25, "a&b.vtu".to_owned())];
+    let output = to_string(|output| write_pvd(output, &files));
+
+    assert_eq!(section(&output, "  <Collection>", 3), [
+        r#"    <DataSet timestep="0" part="0" file="frame_00000.vtu"/>"#,
+        r#"    <DataSet timestep="0.25" part="0" file="a&amp;b.vtu"/>"#,
+        "  </Collection>",
+    ]);
+}
+
+#[test]
+fn series_numbers_frames_and_keeps_the_collection_current() {
+    let directory = common::temp_dir("vtk-series");
+
+    let mut series = VtkSeries::new(directory.path(), "particles", VtkFormat::Legacy).unwrap();
+    let first = series.write_frame(&frame()).unwrap();
+    let second = series.write_frame(&ParticleFrame { time: 1.0, ..frame() }).unwrap();
+
+    assert_eq!(first, directory.join("particles_00000.vtk"));
+    assert_eq!(second, directory.join("particles_00001.vtk"));
+    assert_eq!(std::fs::read_to_string(&second).unwrap(), to_string(|output| write_vtk(output, &ParticleFrame { time: 1.0, ..frame() })));
+
+    let pvd = std::fs::read_to_string(series.pvd_path()).unwrap();
+    assert!(pvd.contains(r#"<DataSet timestep="0.5" part="0" file="particles_00000.vtk"/>"#), "{pvd}");
+    assert!(pvd.contains(r#"<DataSet timestep="1" part="0" file="particles_00001.vtk"/>"#), "{pvd}");
+}